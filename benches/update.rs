@@ -0,0 +1,59 @@
+// Compares updating a mostly settled scene with sleeping chunks against
+// forcing every chunk awake each tick.
+// Run with `cargo bench`.
+use std::time::{Duration, Instant};
+use web_based_rust_sandsim::Grid;
+
+const TICKS: u32 = 200;
+
+fn settled_scene() -> Grid {
+    let mut grid = Grid::with_default_size();
+    let (width, height) = (grid.width(), grid.height());
+
+    // A floor of stone with a pile of sand and a pool of water on top
+    grid.handle_input("e");
+    for y in height - 4..height {
+        grid.set_mouse(0, y);
+        grid.draw_mouse(width - 1, y);
+    }
+    grid.handle_input("q");
+    for y in height / 2..height - 4 {
+        grid.set_mouse(0, y);
+        grid.draw_mouse(width / 2, y);
+    }
+    grid.handle_input("w");
+    for y in height * 3 / 4..height - 4 {
+        grid.set_mouse(width / 2 + 2, y);
+        grid.draw_mouse(width - 1, y);
+    }
+
+    // Let everything come to rest
+    for _ in 0..2000 {
+        grid.update();
+    }
+    grid
+}
+
+fn time_ticks(grid: &mut Grid, wake_all: bool) -> Duration {
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        if wake_all {
+            grid.wake_all();
+        }
+        grid.update();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let mut grid = settled_scene();
+    let sleeping = time_ticks(&mut grid, false);
+    println!("awake chunks after settling: {} of {}", grid.awake_chunks(), grid.width().div_ceil(16) * grid.height().div_ceil(16));
+
+    let mut grid = settled_scene();
+    let all_awake = time_ticks(&mut grid, true);
+
+    println!("{} ticks, sleeping chunks: {:?}", TICKS, sleeping);
+    println!("{} ticks, all chunks awake: {:?}", TICKS, all_awake);
+    println!("speedup: {:.1}x", all_awake.as_secs_f64() / sleeping.as_secs_f64());
+}
@@ -1,31 +1,116 @@
-import init, { Grid } from './pkg/web_based_rust_sandsim.js';
+import init, { Grid, Anchor, ElementType, WindBrush } from './pkg/web_based_rust_sandsim.js';
 
 async function run() {
     await init();
 
     const canvas = document.getElementById('gameCanvas');
     const ctx = canvas.getContext('2d');
-    const gridWidth = 226;
-    const gridHeight = 126;
     const cellSize = 5;
 
 
-    const grid = new Grid(gridWidth, gridHeight);
+    const grid = Grid.with_default_size();
 
-    canvas.width = gridWidth * cellSize;
-    canvas.height = gridHeight * cellSize;
+    function fitCanvas() {
+        canvas.width = grid.width() * cellSize;
+        canvas.height = grid.height() * cellSize;
+    }
+    fitCanvas();
+
+    const anchors = { 'top-left': Anchor.TopLeft, 'centre': Anchor.Centre, 'bottom': Anchor.Bottom };
+    document.getElementById('resizeButton').addEventListener('click', () => {
+        const width = parseInt(document.getElementById('resizeWidth').value);
+        const height = parseInt(document.getElementById('resizeHeight').value);
+        const anchor = anchors[document.getElementById('resizeAnchor').value];
+        grid.resize(width, height, anchor);
+        fitCanvas();
+    });
+    function updateGenerator() {
+        const [x, y] = document.getElementById('generatorDirection').value.split(',').map(Number);
+        const rate = parseFloat(document.getElementById('generatorRate').value);
+        grid.set_generator(x, y, rate);
+    }
+    document.getElementById('generatorDirection').addEventListener('change', updateGenerator);
+    document.getElementById('generatorRate').addEventListener('change', updateGenerator);
+
+    function updateDrains() {
+        if (document.getElementById('drainAbsorbsAll').checked) {
+            grid.set_drain_absorbs_all();
+            return;
+        }
+        let mask = 0;
+        for (const checkbox of document.querySelectorAll('.drainAbsorbs:checked')) {
+            for (const type of checkbox.value.split(' ')) {
+                mask |= 1 << ElementType[type];
+            }
+        }
+        grid.set_drain_absorbs(mask);
+    }
+    for (const checkbox of document.querySelectorAll('.drainAbsorbs, #drainAbsorbsAll')) {
+        checkbox.addEventListener('change', updateDrains);
+    }
+    document.getElementById('resetDrained').addEventListener('click', () => grid.reset_drained());
+
+    function updateSensors() {
+        let mask = 0;
+        for (const checkbox of document.querySelectorAll('.sensorDetects:checked')) {
+            for (const type of checkbox.value.split(' ')) {
+                mask |= 1 << ElementType[type];
+            }
+        }
+        grid.set_sensor_detects(mask);
+    }
+    for (const checkbox of document.querySelectorAll('.sensorDetects')) {
+        checkbox.addEventListener('change', updateSensors);
+    }
+    let sensorsFired = 0;
+
+    document.getElementById('pipeRate').addEventListener('change', (event) => {
+        grid.set_pipe_rate(parseFloat(event.target.value));
+    });
+    document.getElementById('portalChannel').addEventListener('change', (event) => {
+        grid.set_portal_channel(parseInt(event.target.value));
+    });
+    document.getElementById('windBrush').addEventListener('change', (event) => {
+        grid.set_wind_brush(WindBrush[event.target.value]);
+    });
+    document.getElementById('clearWind').addEventListener('click', () => grid.clear_wind());
+
+    document.getElementById('automatonButton').addEventListener('click', () => {
+        const name = document.getElementById('automatonName').value;
+        const rule = document.getElementById('automatonRule').value;
+        const color = document.getElementById('automatonColor').value;
+        const r = parseInt(color.slice(1, 3), 16);
+        const g = parseInt(color.slice(3, 5), 16);
+        const b = parseInt(color.slice(5, 7), 16);
+        try {
+            grid.select_automaton(grid.register_automaton(name, rule, r, g, b));
+            document.getElementById('automatonError').textContent = '';
+        } catch (error) {
+            document.getElementById('automatonError').textContent = error;
+        }
+    });
+    document.getElementById('rescaleButton').addEventListener('click', () => {
+        grid.rescale(2);
+        fitCanvas();
+    });
 
     let mouse_down = false;
     let mouse_pos_x = 0;
     let mouse_pos_y = 0;
 
     canvas.addEventListener('mousedown', (event) => {
-        mouse_down = true;
         const rect = canvas.getBoundingClientRect();
         mouse_pos_x = Math.floor((event.clientX - rect.left) / cellSize);
         mouse_pos_y = Math.floor((event.clientY - rect.top) / cellSize);
+        // Right-click flips switches and valves instead of drawing
+        if (event.button === 2) {
+            grid.toggle_switch(mouse_pos_x, mouse_pos_y);
+            return;
+        }
+        mouse_down = true;
         grid.set_mouse(mouse_pos_x, mouse_pos_y);
     });
+    canvas.addEventListener('contextmenu', (event) => event.preventDefault());
     canvas.addEventListener('mousemove', (event) => {
         const rect = canvas.getBoundingClientRect();
         mouse_pos_x = Math.floor((event.clientX - rect.left) / cellSize);
@@ -45,6 +130,14 @@ async function run() {
             grid.draw_mouse(mouse_pos_x, mouse_pos_y);
         }
         grid.render(ctx, 5);
+        document.getElementById('drainedTotal').textContent = grid.drained_total();
+        const events = grid.take_sensor_events();
+        if (events.length > 0) {
+            sensorsFired += events.length / 2;
+            const [x, y] = events.slice(-2);
+            document.getElementById('sensorEvents').textContent =
+                `sensors fired ${sensorsFired} times, last at (${x}, ${y})`;
+        }
         requestAnimationFrame(update);
     }
 
@@ -0,0 +1,151 @@
+use crate::element::Element;
+
+// A Life-like cellular automaton rule, e.g. B3/S23 for Conway's Life. Rules with more
+// than two states are "Generations" rules: a live cell that fails to survive spends
+// states - 2 ticks dying before it disappears, and dying cells don't count as neighbours.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rule {
+    // Bit n is set if a cell is born / survives with n live neighbours
+    birth: u16,
+    survival: u16,
+    states: u8,
+}
+
+impl Rule {
+    // B3/S12345
+    pub const MAZE: Rule = Rule {
+        birth: 1 << 3,
+        survival: (1 << 1) | (1 << 2) | (1 << 3) | (1 << 4) | (1 << 5),
+        states: 2,
+    };
+
+    // Parse a rulestring in either B/S notation ("B3/S23", "B36/S23", "B2/S/C3") or
+    // S/B notation with an optional state count ("23/3", "/2/3")
+    pub fn parse(rule: &str) -> Result<Rule, String> {
+        let parts: Vec<&str> = rule.trim().split('/').collect();
+        let lettered = parts
+            .iter()
+            .any(|part| part.starts_with(['B', 'b', 'S', 's']));
+        let (birth, survival, states) = if lettered {
+            let mut birth = None;
+            let mut survival = None;
+            let mut states = None;
+            for part in parts {
+                let mut chars = part.chars();
+                match chars.next().map(|c| c.to_ascii_uppercase()) {
+                    Some('B') => birth = Some(chars.as_str()),
+                    Some('S') => survival = Some(chars.as_str()),
+                    Some('C') | Some('G') => states = Some(chars.as_str()),
+                    _ => return Err(format!("Unexpected \"{}\" in rule \"{}\"", part, rule)),
+                }
+            }
+            (birth, survival.unwrap_or(""), states)
+        } else {
+            match parts.as_slice() {
+                [survival, birth] => (Some(*birth), *survival, None),
+                [survival, birth, states] => (Some(*birth), *survival, Some(*states)),
+                _ => return Err(format!("Expected S/B or S/B/C in rule \"{}\"", rule)),
+            }
+        };
+        let birth = birth.ok_or_else(|| format!("Rule \"{}\" has no birth condition", rule))?;
+        let states = match states {
+            Some(states) => states
+                .parse::<u8>()
+                .ok()
+                .filter(|states| *states >= 2)
+                .ok_or_else(|| format!("Invalid number of states \"{}\" in rule \"{}\"", states, rule))?,
+            None => 2,
+        };
+        Ok(Rule {
+            birth: Rule::parse_counts(birth, rule)?,
+            survival: Rule::parse_counts(survival, rule)?,
+            states,
+        })
+    }
+
+    fn parse_counts(counts: &str, rule: &str) -> Result<u16, String> {
+        let mut mask = 0;
+        for c in counts.chars() {
+            match c.to_digit(10) {
+                Some(n) if n <= 8 => mask |= 1 << n,
+                _ => return Err(format!("Invalid neighbour count '{}' in rule \"{}\"", c, rule)),
+            }
+        }
+        Ok(mask)
+    }
+
+    pub fn is_born(&self, neighbours: usize) -> bool {
+        self.birth & (1 << neighbours) != 0
+    }
+
+    pub fn survives(&self, neighbours: usize) -> bool {
+        self.survival & (1 << neighbours) != 0
+    }
+
+    // Number of ticks a cell spends dying before it disappears
+    pub fn dying_states(&self) -> u8 {
+        self.states - 2
+    }
+}
+
+// An automaton element registered with the grid: the rule it follows and the
+// element its live cells are drawn as
+#[derive(Clone, Copy)]
+pub struct Automaton {
+    pub rule: Rule,
+    pub element: &'static Element,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIFE: Rule = Rule {
+        birth: 1 << 3,
+        survival: (1 << 2) | (1 << 3),
+        states: 2,
+    };
+
+    const BRIANS_BRAIN: Rule = Rule {
+        birth: 1 << 2,
+        survival: 0,
+        states: 3,
+    };
+
+    #[test]
+    fn parses_birth_survival_notation() {
+        assert_eq!(Rule::parse("B3/S23"), Ok(LIFE));
+        assert_eq!(Rule::parse("b3/s23"), Ok(LIFE));
+        assert_eq!(Rule::parse(" S23/B3 "), Ok(LIFE));
+        assert_eq!(Rule::parse("B3/S12345"), Ok(Rule::MAZE));
+    }
+
+    #[test]
+    fn parses_survival_birth_notation() {
+        assert_eq!(Rule::parse("23/3"), Ok(LIFE));
+        assert_eq!(Rule::parse("12345/3"), Ok(Rule::MAZE));
+    }
+
+    #[test]
+    fn parses_state_counts() {
+        assert_eq!(Rule::parse("/2/3"), Ok(BRIANS_BRAIN));
+        assert_eq!(Rule::parse("B2/S/C3"), Ok(BRIANS_BRAIN));
+        assert_eq!(Rule::parse("B2/S/G3"), Ok(BRIANS_BRAIN));
+        assert_eq!(BRIANS_BRAIN.dying_states(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for rule in ["", "3", "S23", "B9/S23", "B3/Sx", "X3/S23", "2/3/1", "B3/S23/C", "1/2/3/4"] {
+            assert!(Rule::parse(rule).is_err(), "\"{}\" should not parse", rule);
+        }
+    }
+
+    #[test]
+    fn counts_neighbours() {
+        assert!(LIFE.is_born(3));
+        assert!(!LIFE.is_born(2));
+        assert!(LIFE.survives(2));
+        assert!(!LIFE.survives(4));
+    }
+}
@@ -0,0 +1,67 @@
+pub const CHUNK_SIZE: usize = 16;
+
+// Inclusive bounds of the cells in a chunk that need updating
+#[derive(Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub min_x: usize,
+    pub min_y: usize,
+    pub max_x: usize,
+    pub max_y: usize,
+}
+
+impl Rect {
+    fn point(x: usize, y: usize) -> Rect {
+        Rect { min_x: x, min_y: y, max_x: x, max_y: y }
+    }
+
+    fn include(&mut self, x: usize, y: usize) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
+    }
+
+    pub fn contains_row(&self, y: usize) -> bool {
+        y >= self.min_y && y <= self.max_y
+    }
+
+    pub fn contains_column(&self, x: usize) -> bool {
+        x >= self.min_x && x <= self.max_x
+    }
+}
+
+// A square region of the grid. A chunk sleeps (is skipped by the update) unless
+// something in or next to it changed during the previous tick.
+#[derive(Clone, Copy)]
+pub struct Chunk {
+    // Cells to update this tick
+    pub dirty: Option<Rect>,
+    // Cells that changed this tick and will be updated next tick
+    next_dirty: Option<Rect>,
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk {
+            dirty: None,
+            next_dirty: None,
+        }
+    }
+
+    // Mark a cell (in grid coordinates) as needing an update next tick
+    pub fn wake(&mut self, x: usize, y: usize) {
+        match self.next_dirty.as_mut() {
+            Some(rect) => rect.include(x, y),
+            None => self.next_dirty = Some(Rect::point(x, y)),
+        }
+    }
+
+    // Start a new tick: whatever changed last tick is what gets updated now
+    pub fn advance(&mut self) {
+        self.dirty = self.next_dirty.take();
+    }
+
+    pub fn is_sleeping(&self) -> bool {
+        self.dirty.is_none()
+    }
+}
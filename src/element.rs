@@ -3,7 +3,68 @@
 use wasm_bindgen::prelude::wasm_bindgen;
 
 use crate::Grid;
-use ::rand::{thread_rng, Rng};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use ::rand::{seq::SliceRandom, thread_rng, Rng};
+
+// Default downward acceleration in cells per tick per tick
+pub const GRAVITY: f32 = 0.4;
+// Fastest a particle can fall, in cells per tick
+const TERMINAL_VELOCITY: f32 = 6.0;
+// Fraction of a particle's falling speed that turns sideways when it lands
+const SPLASH: f32 = 0.5;
+// How many cells a liquid spreads sideways per tick when it isn't moving
+const DISPERSION: usize = 5;
+// Speed particles leave a generator at, in cells per tick
+const EMIT_SPEED: f32 = 2.0;
+// Chance that a dying flame leaves smoke behind
+const SMOKE_CHANCE: f32 = 0.25;
+// Chance each tick that a plant touching water grows into it
+const PLANT_GROWTH: f32 = 0.05;
+// Chance that something dissolved by acid gives off fumes
+const FUMES_CHANCE: f32 = 0.5;
+// Speed a blast flings particles at its centre, in cells per tick
+const BLAST_SPEED: f32 = 6.0;
+// Temperature of anything that isn't hotter or colder, in degrees
+const AMBIENT_TEMPERATURE: f32 = 20.0;
+// Fraction of the temperature difference between touching cells that flows each tick
+const CONDUCTION: f32 = 0.1;
+// Temperature differences this small are ignored so settled areas can sleep
+const HEAT_THRESHOLD: f32 = 0.5;
+// Chance each tick that snow with enough snow piled on top compacts into ice
+const SNOW_COMPACTION: f32 = 0.01;
+// How deep snow has to be piled on top of a cell before it compacts
+const SNOW_COMPACTION_DEPTH: usize = 4;
+// What a conductor looks like while a spark passes through it
+const SPARK_HEAD_COLOR: Color = Color { r: 255.0, g: 255.0, b: 180.0 };
+const SPARK_TAIL_COLOR: Color = Color { r: 90.0, g: 140.0, b: 255.0 };
+const SWITCH_ON_COLOR: Color = Color { r: 80.0, g: 255.0, b: 80.0 };
+const SENSOR_ON_COLOR: Color = Color { r: 255.0, g: 80.0, b: 200.0 };
+const VALVE_OPEN_COLOR: Color = Color { r: 120.0, g: 200.0, b: 120.0 };
+// Portals are drawn in one of these colours depending on their channel
+const PORTAL_COLORS: [Color; 6] = [
+    Color { r: 255.0, g: 140.0, b: 0.0 },
+    Color { r: 0.0, g: 160.0, b: 255.0 },
+    Color { r: 200.0, g: 0.0, b: 255.0 },
+    Color { r: 0.0, g: 220.0, b: 140.0 },
+    Color { r: 255.0, g: 0.0, b: 120.0 },
+    Color { r: 220.0, g: 220.0, b: 0.0 },
+];
+// Ticks a gate keeps treating an input as on after a spark reaches it, long enough to
+// bridge the gaps between the sparks a battery sends down a wire
+const INPUT_HOLD_MASK: u16 = 0b111;
+// Ticks a delay line holds a spark before passing it on
+const DELAY_TICKS: u16 = 10;
+// Powders lighter than this get blown about by the wind
+const LIGHT_POWDER_DENSITY: f32 = 1.5;
+// Speed an attractor adds each tick to particles right next to it, falling off to
+// nothing at the edge of its reach
+const PULL_SPEED: f32 = 1.5;
+
+// Offsets of the eight cells surrounding a cell
+const ADJACENT: [(isize, isize); 8] = [(1,1),(0,1),(-1,1),(1,0),(-1,0),(1,-1),(0,-1),(-1,-1)];
+// Offsets of the four cells sharing an edge with a cell, which is how pipes connect
+const ORTHOGONAL: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
 
 #[derive(Clone, Copy, PartialEq)]
 #[wasm_bindgen]
@@ -16,10 +77,64 @@ pub enum ElementType {
     Nothing,
     Magic,
     Fire,
-    Maze,
-    Faucet,
+    Automaton,
     Drain,
+    Plant,
+    Seed,
+    Lava,
+    Acid,
+    Snow,
+    Battery,
+    Gate,
+    Switch,
+    Sensor,
+    Clone,
+    Void,
+    Conveyor,
+    Pipe,
+    Pump,
+    Valve,
+    Portal,
+}
+impl ElementType {
+    // This type's bit in a set of element types
+    pub fn mask(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+// Every element type a drain can absorb: anything but empty space and other drains
+pub const ABSORB_ALL: u32 = !(1 << ElementType::Nothing as u32 | 1 << ElementType::Drain as u32);
+
+// Every element type that flows like a liquid, which is what plain drains absorb and
+// plain sensors detect
+pub const LIQUIDS: u32 =
+    1 << ElementType::Liquid as u32 | 1 << ElementType::Lava as u32 | 1 << ElementType::Acid as u32;
+
+// The electrical state of a conductor. As in Wireworld, a spark head becomes a tail
+// and a tail goes back to idle, so sparks travel along wires without flowing backwards.
+// Unlike Wireworld, power only passes between cells that share an edge.
+#[derive(Clone, Copy, PartialEq)]
+enum Spark {
+    Idle,
+    Head,
+    Tail,
+}
+
+// What a gate does with its inputs. Gates read input A from the cell to their left
+// and input B from the cells above and below, and spark the cell to their right.
+// None of these share an edge, so the inputs can't spark the output wire themselves.
+#[derive(Clone, Copy, PartialEq)]
+enum Logic {
+    None,
+    Not,
+    And,
+    Or,
+    Xor,
+    // Passes sparks from input A on after DELAY_TICKS
+    Delay,
 }
+
 #[derive(Clone, Copy, PartialEq)]
 #[wasm_bindgen]
 pub struct Color {
@@ -33,7 +148,108 @@ pub struct Element {
     pub element_type: ElementType,
     pub color: Color,
     name: &'static str,
-    velocity_x: isize,
+    velocity_x: f32,
+    velocity_y: f32,
+    // How far the particle has moved towards the next cell, so particles slower than
+    // a cell per tick only move every few ticks
+    offset_x: f32,
+    offset_y: f32,
+    // Which of the grid's registered automata this cell follows, if it is one
+    rule: u8,
+    // Ticks a Generations automaton cell has spent dying, 0 while it is alive
+    age: u8,
+    // What a generator or clone emits, which way and the chance of emitting each tick
+    emits: Option<&'static Element>,
+    emit_x: i8,
+    emit_y: i8,
+    rate: f32,
+    // The set of element types a drain absorbs, see ElementType::mask
+    absorbs: u32,
+    // The set of element types that switch a sensor on when they are next to it
+    detects: u32,
+    // Heavier liquids and powders sink through lighter liquids and gases
+    density: f32,
+    // Chance of catching fire each tick while touching fire
+    flammability: f32,
+    // Chance each tick of turning into decays_into (or nothing), e.g. smoke thinning out
+    decay: f32,
+    decays_into: Option<&'static Element>,
+    // How sluggishly a liquid flows, from 0 (water) towards 1
+    viscosity: f32,
+    // Chance each tick of melting into lava while touching fire or lava
+    melting: f32,
+    // Chance each tick of being dissolved while touching acid, 0 for acid-proof elements
+    corrodibility: f32,
+    // How many more cells an acid can dissolve before it is used up
+    strength: u8,
+    // Blast radius when ignited, 0 for things that don't explode
+    explosive: f32,
+    // How much of a blast's force this element shrugs off, 1 for indestructible
+    blast_resistance: f32,
+    // How far away an attractor pulls movable particles in from, 0 for plain voids
+    pull: f32,
+    // Which way a conveyor carries what sits on it: 1 for right, -1 for left
+    // (relative to gravity pulling down)
+    conveys: i8,
+    // The liquid inside a pipe or valve, and which way it last moved
+    contents: Option<&'static Element>,
+    flow_x: i8,
+    flow_y: i8,
+    // Chance each tick that a pipe, pump or valve moves liquid on
+    flow_rate: f32,
+    // Whether a valve has been opened by hand
+    open: bool,
+    // Portals on the same channel are linked to each other
+    channel: u8,
+    temperature: f32,
+    // Heat sources and sinks like fire keep their temperature
+    fixed_temperature: bool,
+    // How readily heat flows through this element, 1 for most things
+    conductivity: f32,
+    // The element this turns into above melts_at (e.g. ice to water, water boiling to
+    // steam) and below freezes_at (e.g. water to ice)
+    melts_at: f32,
+    melts_into: Option<Phase>,
+    freezes_at: f32,
+    freezes_into: Option<Phase>,
+    // Whether sparks travel through this element
+    conductive: bool,
+    // A switch or sensor that is on and a gate whose output is on are spark heads
+    spark: Spark,
+    logic: Logic,
+    // Recent samples of a gate's two inputs, newest in the lowest bit
+    history: [u16; 2],
+}
+
+// An element another one changes phase into. Water and ice turn into each other, so
+// these compare by which element they point at: comparing the elements themselves
+// would go round in circles.
+#[derive(Clone, Copy)]
+struct Phase(&'static Element);
+
+impl PartialEq for Phase {
+    fn eq(&self, other: &Phase) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+// The colour halfway between two others
+fn mix(a: Color, b: Color) -> Color {
+    Color {
+        r: (a.r + b.r) / 2.0,
+        g: (a.g + b.g) / 2.0,
+        b: (a.b + b.b) / 2.0,
+    }
+}
+
+// The neighbouring cell at the given offset, if it is on the grid
+fn neighbour(grid: &Grid, x: usize, y: usize, dx: isize, dy: isize) -> Option<(usize, usize)> {
+    let (nx, ny) = (x as isize + dx, y as isize + dy);
+    if nx >= 0 && ny >= 0 && grid.is_within_bounds(nx as usize, ny as usize) {
+        Some((nx as usize, ny as usize))
+    } else {
+        None
+    }
 }
 
 #[wasm_bindgen]
@@ -51,17 +267,42 @@ impl Element {
         if !grid.is_within_bounds(x, y) {
             return;
         }
+        if self.decay > 0.0 {
+            if thread_rng().gen::<f32>() < self.decay {
+                grid.set(x, y, *self.decays_into.unwrap_or(&NOTHING));
+                return;
+            }
+            // Keep ticking so it decays even if it doesn't move
+            grid.wake(x, y);
+        }
+        if let Some(into) = self.phase_change() {
+            grid.set(x, y, Element { temperature: self.temperature, ..*into });
+            return;
+        }
         match self.element_type {
             ElementType::ImmovableSolid => {},//self.step_immoveable_solid(grid, x, y),
             ElementType::MoveableSolid => self.step_moveable_solid(grid, x, y),
             ElementType::Liquid => self.step_liquid(grid, x, y),
-            ElementType::Gas => {},//self.step_gas(grid, x, y),
+            ElementType::Gas => self.step_gas(grid, x, y),
             ElementType::PixelGenerator => self.step_pixel_generator(grid, x, y),
             ElementType::Magic => self.step_magic(grid, x, y),
             ElementType::Fire => self.step_fire(grid, x, y),
-            ElementType::Maze => self.step_maze(grid, x, y),
-            ElementType::Faucet => self.step_pixel_generator(grid, x, y),
+            ElementType::Automaton => {},// stepped by Grid::step_automata
             ElementType::Drain => self.step_drain(grid, x, y),
+            ElementType::Plant => self.step_plant(grid, x, y),
+            ElementType::Seed => self.step_seed(grid, x, y),
+            ElementType::Lava => self.step_lava(grid, x, y),
+            ElementType::Acid => self.step_acid(grid, x, y),
+            ElementType::Snow => self.step_snow(grid, x, y),
+            // Keep the conductors around a battery or a switch that is on in the electricity pass
+            ElementType::Battery | ElementType::Switch if self.powers(0, 1) => grid.wake_around(x, y),
+            ElementType::Sensor => self.step_sensor(grid, x, y),
+            ElementType::Clone => self.step_clone(grid, x, y),
+            ElementType::Void => self.step_void(grid, x, y),
+            ElementType::Conveyor => self.step_conveyor(grid, x, y),
+            ElementType::Pipe | ElementType::Valve => self.step_pipe(grid, x, y),
+            ElementType::Pump => self.step_pump(grid, x, y),
+            ElementType::Portal => self.step_portal(grid, x, y),
             _ => {}
         }
     }
@@ -70,135 +311,763 @@ impl Element {
     //     // Immoveable solids don't move, no need for implementation here
     // }
 
-    fn step_moveable_solid(&self, grid: &mut Grid, x: usize, y: usize) {
-        // Check if there is space below or liquid to displace
-        if y + 1 < grid.height
-            && (grid.get(x, y + 1).element_type == ElementType::Nothing
-                || grid.get(x, y + 1).element_type == ElementType::Liquid)
-        {
-            grid.move_element(x, y, x, y + 1);
-        } else {
-            // Random movement if no space below
-            let mut options = Vec::new();
-            if y + 1 < grid.height
-                && x > 0
-                && grid.get(x - 1, y + 1).element_type == ElementType::Nothing
-            {
-                options.push((x - 1, y + 1));
+    // Particles can fall into empty space, and powders and liquids sink through
+    // lighter liquids and gases
+    fn can_displace(&self, other: &Element) -> bool {
+        if other.element_type == ElementType::Nothing {
+            return true;
+        }
+        (other.is_liquid() || other.element_type == ElementType::Gas)
+            && (self.is_powder() || self.is_liquid())
+            && self.density > other.density
+    }
+
+    // Follow the velocity one cell at a time and return the furthest cell this particle
+    // can reach, along with whether something blocked it before the end of the path.
+    // Only whole cells are moved; the rest of the distance is kept in the offset.
+    fn march(&mut self, grid: &Grid, x: usize, y: usize) -> (usize, usize, bool) {
+        let distance_x = self.offset_x + self.velocity_x;
+        let distance_y = self.offset_y + self.velocity_y;
+        let (cells_x, cells_y) = (distance_x.trunc(), distance_y.trunc());
+        let steps = cells_x.abs().max(cells_y.abs()) as usize;
+        let (mut end_x, mut end_y) = (x, y);
+        for i in 1..=steps {
+            let t = i as f32 / steps as f32;
+            let path_x = (x as f32 + cells_x * t).round();
+            let path_y = (y as f32 + cells_y * t).round();
+            if path_x < 0.0 || path_y < 0.0 || !grid.is_within_bounds(path_x as usize, path_y as usize) {
+                return self.blocked_at(end_x, end_y);
             }
-            if y + 1 < grid.height
-                && x + 1 < grid.width
-                && grid.get(x + 1, y + 1).element_type == ElementType::Nothing
-            {
-                options.push((x + 1, y + 1));
+            let (path_x, path_y) = (path_x as usize, path_y as usize);
+            if (path_x, path_y) == (end_x, end_y) {
+                continue;
+            }
+            if !self.can_displace(&grid.get(path_x, path_y)) {
+                return self.blocked_at(end_x, end_y);
             }
-            if !options.is_empty() {
-                let random_index = thread_rng().gen_range(0..options.len());
-                let new_pos = options[random_index];
-                grid.move_element(x, y, new_pos.0, new_pos.1);
+            (end_x, end_y) = (path_x, path_y);
+        }
+        // A particle still on its way to the next cell stops if that cell is taken
+        let speed = self.velocity_x.abs().max(self.velocity_y.abs());
+        if steps == 0 && speed > 0.0 {
+            let (ahead_x, ahead_y) = ((self.velocity_x / speed).round() as isize, (self.velocity_y / speed).round() as isize);
+            let free = neighbour(grid, x, y, ahead_x, ahead_y)
+                .is_some_and(|(ahead_x, ahead_y)| self.can_displace(&grid.get(ahead_x, ahead_y)));
+            if !free {
+                return self.blocked_at(x, y);
             }
         }
+        self.offset_x = distance_x - cells_x;
+        self.offset_y = distance_y - cells_y;
+        (end_x, end_y, false)
     }
 
-    fn step_liquid(&mut self, grid: &mut Grid, x: usize, y: usize) {
-        if y + 1 < grid.height && grid.get(x, y + 1).element_type == ElementType::Nothing {
-            // Random movement if nothing below
-            let mut options = Vec::new();
-            if y + 1 < grid.height
-                && x > 0
-                && grid.get(x - 1, y + 1).element_type == ElementType::Nothing
-            {
-                options.push((x - 1, y + 1));
-            }
-            if y + 1 < grid.height
-                && x > 0
-                && grid.get(x + 1, y - 1).element_type == ElementType::Nothing
-            {
-                options.push((x, y + 1));
-            }
-            if y + 1 < grid.height
-                && x + 1 < grid.width
-                && grid.get(x + 1, y + 1).element_type == ElementType::Nothing
-            {
-                options.push((x + 1, y + 1));
+    // Stop partway along a path, losing any progress towards the next cell
+    fn blocked_at(&mut self, x: usize, y: usize) -> (usize, usize, bool) {
+        self.offset_x = 0.0;
+        self.offset_y = 0.0;
+        (x, y, true)
+    }
+
+    // Write this particle back with its new velocity and swap it with whatever is at the new position
+    fn move_to(&self, grid: &mut Grid, x: usize, y: usize, new_x: usize, new_y: usize) {
+        grid.set(x, y, *self);
+        if (x, y) != (new_x, new_y) {
+            grid.swap_elements(x, y, new_x, new_y);
+        }
+    }
+
+    // Accelerate along the grid's gravity and move along the path. Returns false if
+    // the particle couldn't move at all because something is directly in the way.
+    fn fall(&mut self, grid: &mut Grid, x: usize, y: usize) -> bool {
+        // Particles sliding along whatever they rest on lose speed to friction
+        let (down_x, down_y) = grid.down();
+        let supported = (down_x, down_y) != (0, 0)
+            && neighbour(grid, x, y, down_x, down_y)
+                .is_none_or(|(below_x, below_y)| !self.can_displace(&grid.get(below_x, below_y)));
+        if supported {
+            self.slow_down();
+        }
+        self.velocity_x = (self.velocity_x + grid.gravity_x).clamp(-TERMINAL_VELOCITY, TERMINAL_VELOCITY);
+        self.velocity_y = (self.velocity_y + grid.gravity_y).clamp(-TERMINAL_VELOCITY, TERMINAL_VELOCITY);
+        let (new_x, new_y, blocked) = self.march(grid, x, y);
+        if (new_x, new_y) == (x, y) {
+            if blocked || (self.velocity_x == 0.0 && self.velocity_y == 0.0) {
+                return false;
             }
-            if !options.is_empty() {
-                let random_index = thread_rng().gen_range(0..options.len());
-                let new_pos = options[random_index];
-                grid.move_element(x, y, new_pos.0, new_pos.1);
+            // Not a whole cell further yet
+            grid.set(x, y, *self);
+            return true;
+        }
+        if blocked {
+            self.land(grid);
+        }
+        self.move_to(grid, x, y, new_x, new_y);
+        true
+    }
+
+    // Speed across the direction of gravity, positive towards the grid's "side" direction
+    fn sideways_speed(&self, grid: &Grid) -> f32 {
+        let (side_x, side_y) = grid.side();
+        self.velocity_x * side_x as f32 + self.velocity_y * side_y as f32
+    }
+
+    // Turn some of the falling speed into sideways speed when hitting something
+    fn land(&mut self, grid: &Grid) {
+        let (down_x, down_y) = grid.down();
+        let (side_x, side_y) = grid.side();
+        let falling = self.velocity_x * down_x as f32 + self.velocity_y * down_y as f32;
+        let mut sideways = self.sideways_speed(grid);
+        if falling > 1.0 {
+            let direction = if sideways != 0.0 {
+                sideways.signum()
             } else {
-                grid.move_element(x, y, x, y + 1);
+                (thread_rng().gen_range(0..2) * 2 - 1) as f32
+            };
+            sideways += direction * falling * SPLASH;
+        }
+        self.velocity_x = sideways * side_x as f32;
+        self.velocity_y = sideways * side_y as f32;
+    }
+
+    fn slow_down(&mut self) {
+        self.velocity_x *= 0.5;
+        self.velocity_y *= 0.5;
+        if self.velocity_x.abs() < 1.0 && self.velocity_y.abs() < 1.0 {
+            self.velocity_x = 0.0;
+            self.velocity_y = 0.0;
+        }
+    }
+
+    // Cells diagonally below (relative to gravity) we could slide into
+    fn slide_options(&self, grid: &Grid, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let (down_x, down_y) = grid.down();
+        let (side_x, side_y) = grid.side();
+        let mut options = Vec::new();
+        if (down_x, down_y) == (0, 0) {
+            return options;
+        }
+        for direction in [-1, 1] {
+            if let Some((nx, ny)) = neighbour(grid, x, y, down_x + side_x * direction, down_y + side_y * direction) {
+                if self.can_displace(&grid.get(nx, ny)) {
+                    options.push((nx, ny));
+                }
+            }
+        }
+        options
+    }
+
+    fn step_moveable_solid(&mut self, grid: &mut Grid, x: usize, y: usize) {
+        if self.fall(grid, x, y) {
+            return;
+        }
+        self.land(grid);
+
+        // Slide sideways if the landing gave us some speed
+        let sideways = self.sideways_speed(grid);
+        if sideways.abs() >= 1.0 {
+            let direction = sideways.signum() as isize;
+            let (side_x, side_y) = grid.side();
+            self.slow_down();
+            if let Some((new_x, new_y)) = neighbour(grid, x, y, side_x * direction, side_y * direction) {
+                if self.can_displace(&grid.get(new_x, new_y)) {
+                    self.move_to(grid, x, y, new_x, new_y);
+                    return;
+                }
             }
+        }
+        self.velocity_x = 0.0;
+        self.velocity_y = 0.0;
+
+        // Random movement if no space below
+        let options = self.slide_options(grid, x, y);
+        if !options.is_empty() {
+            let random_index = thread_rng().gen_range(0..options.len());
+            let new_pos = options[random_index];
+            self.move_to(grid, x, y, new_pos.0, new_pos.1);
         } else {
-            // Attempt to disperse left or right
+            grid.set(x, y, *self);
+        }
+    }
 
-            let mut rng = rand::thread_rng();
-            let direction = rng.gen_range(0..2) * 2 - 1;
-            let mut current_x = x;
+    fn step_liquid(&mut self, grid: &mut Grid, x: usize, y: usize) {
+        if self.fall(grid, x, y) {
+            return;
+        }
+        self.land(grid);
+
+        // Random movement if nothing diagonally below
+        let options = self.slide_options(grid, x, y);
+        if !options.is_empty() {
+            let random_index = thread_rng().gen_range(0..options.len());
+            let new_pos = options[random_index];
+            self.move_to(grid, x, y, new_pos.0, new_pos.1);
+            return;
+        }
+
+        // Liquids only spread out when something pulls them down
+        let (down_x, down_y) = grid.down();
+        if (down_x, down_y) == (0, 0) {
+            grid.set(x, y, *self);
+            return;
+        }
+
+        // Viscous liquids only spread some of the time, and not as far
+        if thread_rng().gen::<f32>() < self.viscosity {
+            grid.set(x, y, *self);
+            grid.wake(x, y);
+            return;
+        }
+
+        // Attempt to disperse sideways, further if we just landed from a fall.
+        // Try the other side too if the first is blocked, so a liquid that stays
+        // put really has nowhere to go and its chunk can go to sleep.
+        let sideways = self.sideways_speed(grid);
+        let direction = if sideways != 0.0 {
+            sideways.signum() as isize
+        } else {
+            thread_rng().gen_range(0..2) * 2 - 1
+        };
+        let distance = (DISPERSION as f32 * (1.0 - self.viscosity)).round() as usize + sideways.abs() as usize;
+        self.slow_down();
+        let mut end = self.spread(grid, x, y, direction, distance);
+        if end == (x, y) {
+            end = self.spread(grid, x, y, -direction, distance);
+        }
+        self.move_to(grid, x, y, end.0, end.1);
+    }
+
+    // How far a liquid can flow sideways in the given direction before it is
+    // blocked or finds a hole to fall into
+    fn spread(&self, grid: &Grid, x: usize, y: usize, direction: isize, distance: usize) -> (usize, usize) {
+        let (down_x, down_y) = grid.down();
+        let (step_x, step_y) = (grid.side().0 * direction, grid.side().1 * direction);
+        let (mut current_x, mut current_y) = (x, y);
+        for _i in 0..=distance {
+            match neighbour(grid, current_x, current_y, step_x, step_y) {
+                Some((new_x, new_y)) if grid.get(new_x, new_y) == NOTHING => {
+                    (current_x, current_y) = (new_x, new_y);
+                }
+                _ => break,
+            }
+            // Stop spreading once there is a hole to fall into
+            if let Some((below_x, below_y)) = neighbour(grid, current_x, current_y, down_x, down_y) {
+                if grid.get(below_x, below_y) == NOTHING {
+                    break;
+                }
+            }
+        }
+        (current_x, current_y)
+    }
+
+    // Neighbouring cells holding the given element
+    fn touching(&self, grid: &Grid, x: usize, y: usize, kind: &Element) -> Vec<(usize, usize)> {
+        ADJACENT
+            .iter()
+            .filter_map(|&(dx, dy)| neighbour(grid, x, y, dx, dy))
+            .filter(|&(nx, ny)| grid.get(nx, ny).name == kind.name)
+            .collect()
+    }
 
-            for _i in 0..=5 {
-                let new_x: usize = (current_x as i32 + direction as i32) as usize;
+    fn step_plant(&mut self, grid: &mut Grid, x: usize, y: usize) {
+        // Grow into neighbouring water, using it up
+        let wet = self.touching(grid, x, y, &WATER);
+        if wet.is_empty() {
+            return;
+        }
+        let mut rng = thread_rng();
+        if rng.gen::<f32>() < PLANT_GROWTH {
+            let (new_x, new_y) = wet[rng.gen_range(0..wet.len())];
+            grid.set(new_x, new_y, PLANT);
+        } else {
+            // Keep trying while there is water to grow into
+            grid.wake(x, y);
+        }
+    }
 
-                if new_x < grid.width && new_x > 0 {
-                    let target = grid.get(new_x, y);
+    fn step_seed(&mut self, grid: &mut Grid, x: usize, y: usize) {
+        // Sprout once resting on wet ground, otherwise fall like sand
+        let (down_x, down_y) = grid.down();
+        let on_ground = neighbour(grid, x, y, down_x, down_y).is_none_or(|(below_x, below_y)| {
+            let below = grid.get(below_x, below_y);
+            !below.is_liquid() && !matches!(below.element_type, ElementType::Nothing | ElementType::Gas)
+        });
+        if on_ground && !self.touching(grid, x, y, &WATER).is_empty() {
+            grid.set(x, y, PLANT);
+            return;
+        }
+        self.step_moveable_solid(grid, x, y);
+    }
 
-                    if target == NOTHING {
-                        grid.move_element(current_x, y, new_x, y);
-                        current_x = new_x;
-                        //we went sideways! increase velocity if not above max
-                        // break;
+    // Set fire to flammable neighbours and melt meltable ones
+    fn heat_neighbours(&self, grid: &mut Grid, x: usize, y: usize) {
+        let mut rng = thread_rng();
+        for (dx, dy) in ADJACENT {
+            if let Some((nx, ny)) = neighbour(grid, x, y, dx, dy) {
+                let target = grid.get(nx, ny);
+                if rng.gen::<f32>() < target.flammability {
+                    if target.explosive > 0.0 {
+                        grid.detonate(nx, ny);
                     } else {
-                        break;
+                        grid.set(nx, ny, FIRE);
                     }
+                } else if rng.gen::<f32>() < target.melting {
+                    grid.set(nx, ny, LAVA);
+                }
+            }
+        }
+    }
+
+    fn step_lava(&mut self, grid: &mut Grid, x: usize, y: usize) {
+        // Water touching lava boils away and the lava hardens into obsidian
+        let wet = self.touching(grid, x, y, &WATER);
+        if !wet.is_empty() {
+            for (wet_x, wet_y) in wet {
+                grid.set(wet_x, wet_y, STEAM);
+            }
+            grid.set(x, y, OBSIDIAN);
+            return;
+        }
+        self.heat_neighbours(grid, x, y);
+        self.step_liquid(grid, x, y);
+    }
+
+    fn step_acid(&mut self, grid: &mut Grid, x: usize, y: usize) {
+        // Dissolve neighbours, each with its own chance, using up some of the acid
+        let mut rng = thread_rng();
+        let mut corroding = false;
+        for (dx, dy) in ADJACENT {
+            if let Some((nx, ny)) = neighbour(grid, x, y, dx, dy) {
+                let target = grid.get(nx, ny);
+                if target.corrodibility == 0.0 {
+                    continue;
+                }
+                if rng.gen::<f32>() >= target.corrodibility {
+                    corroding = true;
+                    continue;
+                }
+                let residue = if rng.gen::<f32>() < FUMES_CHANCE { FUMES } else { NOTHING };
+                grid.set(nx, ny, residue);
+                self.strength -= 1;
+                if self.strength == 0 {
+                    grid.set(x, y, FUMES);
+                    return;
+                }
+            }
+        }
+        if corroding {
+            // Keep eating away at whatever survived this tick
+            grid.wake(x, y);
+        }
+        self.step_liquid(grid, x, y);
+    }
+
+    fn is_liquid(&self) -> bool {
+        self.element_type.mask() & LIQUIDS != 0
+    }
+
+    // Grains that fall and pile up like sand
+    fn is_powder(&self) -> bool {
+        matches!(self.element_type, ElementType::MoveableSolid | ElementType::Snow | ElementType::Seed)
+    }
+
+    fn is_light_powder(&self) -> bool {
+        self.is_powder() && self.density < LIGHT_POWDER_DENSITY
+    }
+
+    // Particles that move under gravity and can be flung by a blast
+    fn is_movable(&self) -> bool {
+        matches!(
+            self.element_type,
+            ElementType::MoveableSolid
+                | ElementType::Liquid
+                | ElementType::Seed
+                | ElementType::Lava
+                | ElementType::Acid
+                | ElementType::Snow
+        )
+    }
+
+    // The element this melts or freezes into at its current temperature, if any
+    fn phase_change(&self) -> Option<&'static Element> {
+        match (self.melts_into, self.freezes_into) {
+            (Some(into), _) if self.temperature > self.melts_at => Some(into.0),
+            (_, Some(into)) if self.temperature < self.freezes_at => Some(into.0),
+            _ => None,
+        }
+    }
+
+    fn step_snow(&mut self, grid: &mut Grid, x: usize, y: usize) {
+        // Snow buried under enough snow gets packed into ice
+        let (down_x, down_y) = grid.down();
+        let (mut above_x, mut above_y) = (x, y);
+        let mut depth = 0;
+        while depth < SNOW_COMPACTION_DEPTH {
+            match neighbour(grid, above_x, above_y, -down_x, -down_y) {
+                Some((next_x, next_y)) if (down_x, down_y) != (0, 0) && grid.get(next_x, next_y).name == self.name => {
+                    (above_x, above_y) = (next_x, next_y);
+                    depth += 1;
                 }
+                _ => break,
+            }
+        }
+        if depth == SNOW_COMPACTION_DEPTH {
+            if thread_rng().gen::<f32>() < SNOW_COMPACTION {
+                grid.set(x, y, Element { temperature: self.temperature, ..ICE });
+                return;
             }
+            grid.wake(x, y);
         }
+        self.step_moveable_solid(grid, x, y);
     }
 
     fn step_magic(&mut self, grid: &mut Grid, x: usize, y: usize) {
+        // Magic floats against gravity and wanders sideways
+        let (down_x, down_y) = grid.down();
+        let (side_x, side_y) = grid.side();
         let mut offset: (isize, isize) = (0, 0);
-        if y > 1 && y < grid.height {
-            let above = grid.get(x, y - 1);
+        if let Some((above_x, above_y)) = neighbour(grid, x, y, -down_x, -down_y) {
+            let above = grid.get(above_x, above_y);
             if above.element_type == ElementType::Nothing
                 || above.element_type == ElementType::Liquid
             {
-                offset = (0, -1);
+                offset = (-down_x, -down_y);
             }
         }
         let mut rng = rand::thread_rng();
         let direction = rng.gen_range(0..2) * 2 - 1;
-        let new_x: isize = x as isize + direction;
-        if new_x >= 0 && new_x <= (grid.width - 1) as isize {
-            let target = grid.get(new_x as usize, (y as isize + offset.1) as usize);
+        if let Some((new_x, new_y)) = neighbour(grid, x, y, offset.0 + side_x * direction, offset.1 + side_y * direction) {
+            let target = grid.get(new_x, new_y);
             if target.element_type == ElementType::Nothing
                 || target.element_type == ElementType::Liquid
             {
-                offset = (new_x, offset.1);
+                offset = (offset.0 + side_x * direction, offset.1 + side_y * direction);
             }
         }
-        let (x2, y2) = (x as isize + offset.0, y as isize + offset.1);
-        let target = grid.get(x2 as usize, y2 as usize);
+        let (x2, y2) = ((x as isize + offset.0) as usize, (y as isize + offset.1) as usize);
+        let target = grid.get(x2, y2);
         grid.set(x, y, target);
-        grid.set(x2 as usize, y2 as usize, MAGIC);
+        grid.set(x2, y2, MAGIC);
     }
 
-    // fn step_gas(&mut self, grid: &mut Grid, x: usize, y: usize) {
-    //     // Gas simulation logic
-    // }
+    fn step_gas(&mut self, grid: &mut Grid, x: usize, y: usize) {
+        // Gases drift upwards (against gravity), or in any direction without gravity
+        let mut rng = thread_rng();
+        let (down_x, down_y) = grid.down();
+        let (side_x, side_y) = grid.side();
+        let options = if (down_x, down_y) == (0, 0) {
+            let (dx, dy) = ADJACENT[rng.gen_range(0..ADJACENT.len())];
+            vec![(dx, dy)]
+        } else {
+            let direction = rng.gen_range(0..2) * 2 - 1;
+            vec![
+                (-down_x, -down_y),
+                (side_x * direction - down_x, side_y * direction - down_y),
+                (side_x * direction, side_y * direction),
+            ]
+        };
+        for (dx, dy) in options {
+            if let Some((new_x, new_y)) = neighbour(grid, x, y, dx, dy) {
+                if grid.get(new_x, new_y) == NOTHING {
+                    grid.move_element(x, y, new_x, new_y);
+                    return;
+                }
+            }
+        }
+    }
 
     fn step_pixel_generator(&self, grid: &mut Grid, x: usize, y: usize) {
-        // Check if there is air below
-        if y + 1 < grid.height && grid.get(x, y + 1).element_type == ElementType::Nothing {
-            grid.set(x, y + 1, WATER);
+        if self.emit_target(grid, x, y).is_none() {
+            return;
+        }
+        if thread_rng().gen::<f32>() < self.rate {
+            self.emit(grid, x, y);
+        } else {
+            // Nothing changed, but keep trying next tick
+            grid.wake(x, y);
+        }
+    }
+
+    // Where a generator emits, if there is air there
+    fn emit_target(&self, grid: &Grid, x: usize, y: usize) -> Option<(usize, usize)> {
+        neighbour(grid, x, y, self.emit_x as isize, self.emit_y as isize)
+            .filter(|&(new_x, new_y)| grid.get(new_x, new_y) == NOTHING)
+    }
+
+    fn emit(&self, grid: &mut Grid, x: usize, y: usize) {
+        if let (Some(emits), Some((new_x, new_y))) = (self.emits, self.emit_target(grid, x, y)) {
+            let mut particle = *emits;
+            particle.velocity_x = self.emit_x as f32 * EMIT_SPEED;
+            particle.velocity_y = self.emit_y as f32 * EMIT_SPEED;
+            grid.set(new_x, new_y, particle);
         }
     }
     fn step_drain(&mut self, grid: &mut Grid, x: usize, y: usize){
-        //turn adjacent liquids into nothing
-        let adj = [(1,1),(0,1),(-1,1),(1,0),(-1,0),(1,-1),(0,-1),(-1,-1)];
-        for (l,r) in adj {
-            if grid.get((x as isize + l) as usize,(y as isize + r) as usize).element_type == ElementType::Liquid{
-                grid.set((x as isize + l) as usize,(y as isize + r) as usize, NOTHING);
+        //turn adjacent elements this drain absorbs into nothing, counting what was eaten
+        for (l,r) in ADJACENT {
+            if let Some((nx, ny)) = neighbour(grid, x, y, l, r) {
+                let target = grid.get(nx, ny);
+                if self.absorbs & target.element_type.mask() != 0 {
+                    grid.count_drained(&target);
+                    grid.set(nx, ny, NOTHING);
+                }
+            }
+        }
+    }
+
+    // Learn the first element that touches this clone, then copy it into every empty
+    // cell around it
+    fn step_clone(&self, grid: &mut Grid, x: usize, y: usize) {
+        let emits = match self.emits {
+            Some(emits) => emits,
+            None => {
+                let learned = ADJACENT
+                    .iter()
+                    .filter_map(|&(dx, dy)| neighbour(grid, x, y, dx, dy))
+                    .map(|(nx, ny)| grid.get(nx, ny))
+                    .filter(|cell| !matches!(cell.element_type, ElementType::Nothing | ElementType::Clone))
+                    .find_map(|cell| grid.species(&cell));
+                if let Some(emits) = learned {
+                    let color = mix(CLONE.color, emits.color);
+                    grid.set(x, y, Element { emits: Some(emits), color, ..*self });
+                }
+                return;
+            }
+        };
+        for (dx, dy) in ADJACENT {
+            if let Some((nx, ny)) = neighbour(grid, x, y, dx, dy) {
+                if grid.get(nx, ny) == NOTHING {
+                    grid.set(nx, ny, *emits);
+                }
+            }
+        }
+    }
+
+    // Delete everything next to this that isn't another void. Attractors also pull
+    // the movable particles around them in.
+    fn step_void(&self, grid: &mut Grid, x: usize, y: usize) {
+        for (dx, dy) in ADJACENT {
+            if let Some((nx, ny)) = neighbour(grid, x, y, dx, dy) {
+                let target = grid.get(nx, ny);
+                if !matches!(target.element_type, ElementType::Nothing | ElementType::Void) {
+                    grid.set(nx, ny, NOTHING);
+                }
+            }
+        }
+        if self.pull > 0.0 {
+            grid.attract(x, y);
+        }
+    }
+
+    // Speed up the movable particles within reach of this attractor towards it, more
+    // strongly the closer they are
+    pub(crate) fn pull_in(&self, grid: &mut Grid, x: usize, y: usize) {
+        if self.pull <= 0.0 {
+            return;
+        }
+        let reach = self.pull.ceil() as isize;
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                if distance == 0.0 || distance > self.pull {
+                    continue;
+                }
+                let (nx, ny) = match neighbour(grid, x, y, dx, dy) {
+                    Some(position) => position,
+                    None => continue,
+                };
+                let mut target = grid.get(nx, ny);
+                if target.is_movable() {
+                    let pull = PULL_SPEED * (1.0 - distance / self.pull) / distance;
+                    target.velocity_x -= dx as f32 * pull;
+                    target.velocity_y -= dy as f32 * pull;
+                    grid.set(nx, ny, target);
+                }
+            }
+        }
+        // Keep pulling in whatever comes within reach
+        grid.wake(x, y);
+    }
+
+    // Carry the particle sitting on top of this conveyor one cell along, unless it
+    // already moved this tick (e.g. carried here by the previous belt cell)
+    fn step_conveyor(&self, grid: &mut Grid, x: usize, y: usize) {
+        let (down_x, down_y) = grid.down();
+        if (down_x, down_y) == (0, 0) {
+            return;
+        }
+        let (top_x, top_y) = match neighbour(grid, x, y, -down_x, -down_y) {
+            Some(position) => position,
+            None => return,
+        };
+        if !grid.get(top_x, top_y).is_movable() || grid.moved_this_tick(top_x, top_y) {
+            return;
+        }
+        let (side_x, side_y) = grid.side();
+        let direction = -self.conveys as isize;
+        if let Some((new_x, new_y)) = neighbour(grid, top_x, top_y, side_x * direction, side_y * direction) {
+            if grid.get(new_x, new_y) == NOTHING {
+                grid.move_element(top_x, top_y, new_x, new_y);
+            }
+        }
+    }
+
+    // Pipes and valves, which liquid flows through
+    fn is_plumbing(&self) -> bool {
+        matches!(self.element_type, ElementType::Pipe | ElementType::Valve)
+    }
+
+    // Whether liquid can flow into this cell: an empty pipe, or an empty valve that is open
+    fn accepts_liquid(&self, grid: &Grid, x: usize, y: usize) -> bool {
+        match self.element_type {
+            ElementType::Pipe => self.contents.is_none(),
+            ElementType::Valve => self.contents.is_none() && self.is_open(grid, x, y),
+            _ => false,
+        }
+    }
+
+    // A valve is open while it has been opened by hand or something powers it
+    fn is_open(&self, grid: &Grid, x: usize, y: usize) -> bool {
+        self.open || ADJACENT.iter().any(|&(dx, dy)| powered_from(grid, x, y, dx, dy))
+    }
+
+    // This pipe or valve holding the given liquid, or empty
+    fn filled(&self, contents: Option<&'static Element>, flow_x: i8, flow_y: i8) -> Element {
+        let color = match (self.element_type, contents) {
+            (ElementType::Pipe, Some(contents)) => mix(PIPE.color, contents.color),
+            (ElementType::Pipe, None) => PIPE.color,
+            _ => self.color,
+        };
+        Element { contents, flow_x, flow_y, color, ..*self }
+    }
+
+    // Pass the liquid in this pipe on to a connected empty pipe, avoiding the way it came
+    // so it keeps flowing along the network. At the end of a pipe it pours out into the
+    // air, and only if that's blocked too does it turn back.
+    fn step_pipe(&self, grid: &mut Grid, x: usize, y: usize) {
+        let contents = match self.contents {
+            Some(contents) => contents,
+            None => return,
+        };
+        if self.element_type == ElementType::Valve && !self.is_open(grid, x, y) {
+            return;
+        }
+        let mut rng = thread_rng();
+        if rng.gen::<f32>() >= self.flow_rate {
+            grid.wake(x, y);
+            return;
+        }
+        let mut directions = ORTHOGONAL;
+        directions.shuffle(&mut rng);
+        let backwards = (-(self.flow_x as isize), -(self.flow_y as isize));
+        let onwards: Vec<(isize, isize)> = directions.into_iter().filter(|&direction| direction != backwards).collect();
+        let connected = |(dx, dy): (isize, isize)| {
+            neighbour(grid, x, y, dx, dy).is_some_and(|(nx, ny)| grid.get(nx, ny).is_plumbing())
+        };
+        let into_pipe = |direction: (isize, isize)| {
+            let (dx, dy) = direction;
+            neighbour(grid, x, y, dx, dy)
+                .filter(|&(nx, ny)| grid.get(nx, ny).accepts_liquid(grid, nx, ny))
+                .map(|position| (direction, position, true))
+        };
+        let into_air = |direction: (isize, isize)| {
+            let (dx, dy) = direction;
+            neighbour(grid, x, y, dx, dy)
+                .filter(|&(nx, ny)| grid.get(nx, ny) == NOTHING)
+                .map(|position| (direction, position, false))
+        };
+        // Wait for a full pipe ahead to empty rather than leaking out of the middle of a pipe
+        let target = if onwards.iter().any(|&direction| connected(direction)) {
+            onwards.iter().find_map(|&direction| into_pipe(direction))
+        } else {
+            onwards.iter().find_map(|&direction| into_air(direction)).or_else(|| into_pipe(backwards))
+        };
+        let ((dx, dy), (nx, ny), into_pipe) = match target {
+            Some(target) => target,
+            None => return,
+        };
+        if into_pipe {
+            let next = grid.get(nx, ny).filled(Some(contents), dx as i8, dy as i8);
+            grid.set(nx, ny, next);
+        } else {
+            grid.set(nx, ny, *contents);
+        }
+        grid.set(x, y, self.filled(None, 0, 0));
+    }
+
+    // Pull liquid from around the pump into an empty pipe joined to it
+    fn step_pump(&self, grid: &mut Grid, x: usize, y: usize) {
+        let source = ADJACENT.iter().copied().find_map(|(dx, dy)| {
+            neighbour(grid, x, y, dx, dy).filter(|&(nx, ny)| grid.get(nx, ny).is_liquid())
+        });
+        let pipe = ORTHOGONAL.iter().copied().find(|&(dx, dy)| {
+            neighbour(grid, x, y, dx, dy).is_some_and(|(nx, ny)| grid.get(nx, ny).accepts_liquid(grid, nx, ny))
+        });
+        let (source_x, source_y, (dx, dy)) = match (source, pipe) {
+            (Some((source_x, source_y)), Some(direction)) => (source_x, source_y, direction),
+            _ => return,
+        };
+        if thread_rng().gen::<f32>() >= self.flow_rate {
+            grid.wake(x, y);
+            return;
+        }
+        let liquid = grid.get(source_x, source_y);
+        if let Some(species) = grid.species(&liquid) {
+            let (nx, ny) = ((x as isize + dx) as usize, (y as isize + dy) as usize);
+            let next = grid.get(nx, ny).filled(Some(species), dx as i8, dy as i8);
+            grid.set(nx, ny, next);
+            grid.set(source_x, source_y, NOTHING);
+        }
+    }
+
+    // Send the particles going into this portal out of a linked portal on the same
+    // channel, still moving the same way. A particle goes in if it is moving towards
+    // the portal or resting on top of it.
+    fn step_portal(&self, grid: &mut Grid, x: usize, y: usize) {
+        let mut rng = thread_rng();
+        for (dx, dy) in ORTHOGONAL {
+            let (particle_x, particle_y) = match neighbour(grid, x, y, dx, dy) {
+                Some(position) => position,
+                None => continue,
+            };
+            let particle = grid.get(particle_x, particle_y);
+            if !particle.is_movable() || grid.moved_this_tick(particle_x, particle_y) {
+                continue;
+            }
+            let (in_x, in_y) = (-dx, -dy);
+            let towards = particle.velocity_x * in_x as f32 + particle.velocity_y * in_y as f32;
+            if towards <= 0.0 && (in_x, in_y) != grid.down() {
+                continue;
+            }
+            match grid.portal_exits(x, y, in_x, in_y).choose(&mut rng) {
+                Some(&(exit_x, exit_y)) => {
+                    grid.set(particle_x, particle_y, NOTHING);
+                    grid.set(exit_x, exit_y, particle);
+                }
+                // Try again once the way out is clear
+                None => grid.wake(x, y),
+            }
+        }
+    }
+
+    // Switch on while something this sensor detects is next to it, powering the cells
+    // around it like a switch
+    fn step_sensor(&self, grid: &mut Grid, x: usize, y: usize) {
+        let detected = ADJACENT
+            .iter()
+            .filter_map(|&(dx, dy)| neighbour(grid, x, y, dx, dy))
+            .any(|(nx, ny)| self.detects & grid.get(nx, ny).element_type.mask() != 0);
+        if detected == (self.spark == Spark::Head) {
+            if detected {
+                grid.wake_around(x, y);
             }
+            return;
+        }
+        if detected {
+            grid.sensor_triggered(x, y);
+            grid.set(x, y, Element { spark: Spark::Head, color: SENSOR_ON_COLOR, ..*self });
+        } else {
+            grid.set(x, y, Element { spark: Spark::Idle, color: SENSOR.color, ..*self });
         }
     }
 
@@ -206,99 +1075,417 @@ impl Element {
         let mut rng = thread_rng();
         let upward_chance = 0.7;
 
-        // Check if the pixel above is empty and within grid bounds
-        if y > 0 && grid.get(x, y - 1) == NOTHING {
-            // Move upward with a chance based on upward_chance
-            if rng.gen::<f32>() < upward_chance {
-                grid.move_element(x, y, x, y - 1);
+        self.heat_neighbours(grid, x, y);
+
+        // Check if the pixel above (against gravity) is empty and within grid bounds
+        let (down_x, down_y) = grid.down();
+        if let Some((above_x, above_y)) = neighbour(grid, x, y, -down_x, -down_y) {
+            if grid.get(above_x, above_y) == NOTHING && rng.gen::<f32>() < upward_chance {
+                // Move upward with a chance based on upward_chance
+                grid.move_element(x, y, above_x, above_y);
                 return; // Fire moves only once per step
             }
         }
 
         // If no upward movement occurred, the fire drifts randomly
         let drift_direction = rng.gen_range(-1..=1); // -1 for left, 0 for no drift, 1 for right
-        let new_x = (x as i32 + drift_direction) as usize;
+        let (side_x, side_y) = grid.side();
+        let drift = neighbour(grid, x, y, side_x * drift_direction, side_y * drift_direction);
 
         // Check if the new position is within grid bounds and empty
-        if new_x < grid.width && grid.get(new_x, y) == NOTHING {
-            grid.move_element(x, y, new_x, y);
+        if let Some((new_x, new_y)) = drift.filter(|&(new_x, new_y)| grid.get(new_x, new_y) == NOTHING) {
+            grid.move_element(x, y, new_x, new_y);
         } else {
-            // If no movement is possible, the fire dies out, turning into NOTHING
-            grid.set(x, y, NOTHING);
+            // If no movement is possible, the fire dies out, sometimes leaving smoke
+            if rng.gen::<f32>() < SMOKE_CHANCE {
+                grid.set(x, y, SMOKE);
+            } else {
+                grid.set(x, y, NOTHING);
+            }
         }
     }
+}
 
-    pub fn step_maze(&mut self, grid: &mut Grid, x: usize, y: usize) {
-        // Check all neighboring cells
-        for dx in -1..=1 {
-            for dy in -1..=1 {
-                // Skip the current cell
-                if dx == 0 && dy == 0 {
-                    continue;
-                }
-
-                let nx = x as i32 + dx;
-                let ny = y as i32 + dy;
-
-                // Check if the neighboring cell is within the grid bounds
-                if nx >= 0 && nx < grid.width as i32 && ny >= 0 && ny < grid.height as i32 {
-                    let neighbor = grid.get(nx as usize, ny as usize);
+// Let heat flow between the cell at the given position and its right and lower
+// neighbours. Empty space doesn't conduct heat.
+pub(crate) fn conduct_heat(grid: &mut Grid, x: usize, y: usize) {
+    let mut cell = grid.get(x, y);
+    if cell.element_type == ElementType::Nothing {
+        return;
+    }
+    for (dx, dy) in [(1, 0), (0, 1)] {
+        let (nx, ny) = match neighbour(grid, x, y, dx, dy) {
+            Some(position) => position,
+            None => continue,
+        };
+        let mut other = grid.get(nx, ny);
+        let difference = other.temperature - cell.temperature;
+        if other.element_type == ElementType::Nothing || difference.abs() <= HEAT_THRESHOLD {
+            continue;
+        }
+        let flow = difference * CONDUCTION * cell.conductivity.min(other.conductivity);
+        if !cell.fixed_temperature {
+            cell.temperature += flow;
+        }
+        if !other.fixed_temperature {
+            other.temperature -= flow;
+            grid.set(nx, ny, other);
+        }
+    }
+    grid.set(x, y, cell);
+}
 
-                    // Check if the neighboring cell is not a maze cell
-                    if neighbor != MAZE {
-                        // Check if the neighboring cell has 3 neighbors
-                        let neighbor_neighbors = self.count_maze_neighbors(grid, nx as usize, ny as usize);
+// Whether the neighbour at the given offset powers the cell at the given position
+fn powered_from(grid: &Grid, x: usize, y: usize, dx: isize, dy: isize) -> bool {
+    neighbour(grid, x, y, dx, dy).is_some_and(|(nx, ny)| grid.get(nx, ny).powers(-dx, -dy))
+}
 
-                        // Set the neighboring cell to maze if it has 3 neighbors
-                        if neighbor_neighbors == 3 {
-                            grid.set(nx as usize, ny as usize, MAZE);
-                        }
-                    }
+// Let liquid in connected vessels find a common level, as hydrostatic pressure would.
+// If the body of liquid the given cell belongs to has free surfaces at different
+// heights, its highest resting surface loses a particle to the air just above its
+// lowest one, so e.g. water poured into one side of a U-tube rises up the other side.
+// Cells of bodies that have already been evened out this tick are in visited.
+pub(crate) fn equalise_levels(grid: &mut Grid, x: usize, y: usize, visited: &mut HashSet<(usize, usize)>) {
+    let cell = grid.get(x, y);
+    let (down_x, down_y) = grid.down();
+    if !cell.is_liquid() || (down_x, down_y) == (0, 0) || !visited.insert((x, y)) {
+        return;
+    }
+    let height = |(x, y): (usize, usize)| -(x as isize * down_x + y as isize * down_y);
+    // The highest liquid cells with air above them, not counting drops still falling (with
+    // nothing under them, or only something that is moving too), and the lowest air cells
+    // with liquid below them. Every cell tied for highest or lowest is kept so the one
+    // moved is picked at random, not by the order of the flood.
+    let mut highest: Vec<(usize, usize)> = Vec::new();
+    let mut lowest: Vec<(usize, usize)> = Vec::new();
+    let mut unvisited = vec![(x, y)];
+    while let Some((current_x, current_y)) = unvisited.pop() {
+        for (dx, dy) in ORTHOGONAL {
+            if let Some((nx, ny)) = neighbour(grid, current_x, current_y, dx, dy) {
+                if grid.get(nx, ny).name == cell.name && visited.insert((nx, ny)) {
+                    unvisited.push((nx, ny));
                 }
             }
         }
-        // Check the current cell
-        if grid.get(x, y) == MAZE {
-            let current_neighbors = self.count_maze_neighbors(grid, x, y);
-
-            // Set the current cell to nothing if it has less than 1 or more than 5 neighbors
-            if current_neighbors < 1 || current_neighbors > 5 {
-                grid.set(x, y, NOTHING);
+        let above = match neighbour(grid, current_x, current_y, -down_x, -down_y) {
+            Some(above) if grid.get(above.0, above.1) == NOTHING => above,
+            _ => continue,
+        };
+        let lower = lowest.first().map_or(Ordering::Greater, |&lowest| height(lowest).cmp(&height(above)));
+        keep_furthest(&mut lowest, above, lower);
+        let falling = match neighbour(grid, current_x, current_y, down_x, down_y) {
+            Some((below_x, below_y)) => {
+                grid.get(below_x, below_y) == NOTHING || grid.moved_this_tick(below_x, below_y)
             }
+            None => false,
+        };
+        if !falling {
+            let current = (current_x, current_y);
+            let higher = highest.first().map_or(Ordering::Greater, |&highest| height(current).cmp(&height(highest)));
+            keep_furthest(&mut highest, current, higher);
         }
     }
-    
-    fn count_maze_neighbors(&mut self, grid: &Grid, x: usize, y: usize) -> usize {
-        let mut neighbor_neighbors = 0;
-    
-        for dx in -1..=1 {
-            for dy in -1..=1 {
-                // Skip the current cell and the neighboring cell
-                if (dx == 0 && dy == 0) || (dx == 0 && dy == 0) {
-                    continue;
-                }
-    
-                let nx = x as i32 + dx;
-                let ny = y as i32 + dy;
-    
-                // Check if the neighbor of the current cell is within the grid bounds
-                if nx >= 0 && nx < grid.width as i32 && ny >= 0 && ny < grid.height as i32 {
-                    let neighbor = grid.get(nx as usize, ny as usize);
-    
-                    // Check if the neighbor of the current cell is a maze cell
-                    if neighbor == MAZE {
-                        neighbor_neighbors += 1;
-                    }
-                }
-            }
+    // Moving a particle between surfaces only one cell apart would just swap which is higher
+    let mut rng = thread_rng();
+    if let (Some(&(high_x, high_y)), Some(&(low_x, low_y))) = (highest.choose(&mut rng), lowest.choose(&mut rng)) {
+        if height((high_x, high_y)) > height((low_x, low_y)) {
+            grid.move_element(high_x, high_y, low_x, low_y);
         }
-    
-        neighbor_neighbors
     }
 }
 
+// Add a cell to the ones found so far that are furthest in some direction, given
+// whether it is further than them, level with them or not as far
+fn keep_furthest(furthest: &mut Vec<(usize, usize)>, cell: (usize, usize), compared: Ordering) {
+    match compared {
+        Ordering::Greater => {
+            furthest.clear();
+            furthest.push(cell);
+        }
+        Ordering::Equal => furthest.push(cell),
+        Ordering::Less => {}
+    }
+}
+
+// What the conductor or gate at the given position becomes in the next electrical
+// generation, or None if it stays the same. An idle conductor sparks when one or two
+// of its neighbours power it.
+pub(crate) fn next_charge(grid: &Grid, x: usize, y: usize) -> Option<Element> {
+    let cell = grid.get(x, y);
+    if cell.element_type == ElementType::Gate {
+        return cell.next_gate(grid, x, y);
+    }
+    if !cell.conductive {
+        return None;
+    }
+    let spark = match cell.spark {
+        Spark::Head => Spark::Tail,
+        Spark::Tail => Spark::Idle,
+        Spark::Idle => {
+            let powered = ADJACENT
+                .iter()
+                .filter(|&&(dx, dy)| powered_from(grid, x, y, dx, dy))
+                .count();
+            if powered != 1 && powered != 2 {
+                return None;
+            }
+            Spark::Head
+        }
+    };
+    let color = match spark {
+        Spark::Head => SPARK_HEAD_COLOR,
+        Spark::Tail => SPARK_TAIL_COLOR,
+        Spark::Idle => cell.species().map_or(cell.color, |species| species.color),
+    };
+    Some(Element { spark, color, ..cell })
+}
+
+// Constructors and helpers used by the grid
+impl Element {
+    // Whether this cell powers the cell at the given offset from it. Batteries, switches
+    // that are on and spark heads power the four cells next to them, gates only their output.
+    fn powers(&self, dx: isize, dy: isize) -> bool {
+        if dx != 0 && dy != 0 {
+            return false;
+        }
+        match self.element_type {
+            ElementType::Battery => true,
+            ElementType::Gate => self.spark == Spark::Head && (dx, dy) == (1, 0),
+            _ => self.spark == Spark::Head,
+        }
+    }
+
+    // The gate with its inputs sampled and its output worked out, or None if it
+    // stays the same
+    fn next_gate(&self, grid: &Grid, x: usize, y: usize) -> Option<Element> {
+        let a = powered_from(grid, x, y, -1, 0);
+        let b = powered_from(grid, x, y, 0, -1) || powered_from(grid, x, y, 0, 1);
+        let history = [(self.history[0] << 1) | a as u16, (self.history[1] << 1) | b as u16];
+        let (a, b) = (history[0] & INPUT_HOLD_MASK != 0, history[1] & INPUT_HOLD_MASK != 0);
+        let on = match self.logic {
+            Logic::None => false,
+            Logic::Not => !a,
+            Logic::And => a && b,
+            Logic::Or => a || b,
+            Logic::Xor => a != b,
+            Logic::Delay => history[0] & (1 << DELAY_TICKS) != 0,
+        };
+        let spark = if on { Spark::Head } else { Spark::Idle };
+        let next = Element { history, spark, ..*self };
+        if next == *self {
+            None
+        } else {
+            Some(next)
+        }
+    }
+
+    // Set off the explosives and fire the generators that a spark head or a gate
+    // that just turned on powers
+    pub(crate) fn discharge(&self, grid: &mut Grid, x: usize, y: usize) {
+        for (dx, dy) in ADJACENT {
+            if !self.powers(dx, dy) {
+                continue;
+            }
+            if let Some((nx, ny)) = neighbour(grid, x, y, dx, dy) {
+                let target = grid.get(nx, ny);
+                if target.explosive > 0.0 {
+                    grid.detonate(nx, ny);
+                } else if target.element_type == ElementType::PixelGenerator {
+                    target.emit(grid, nx, ny);
+                }
+            }
+        }
+    }
+
+    // The switch or valve flipped the other way, or None if this is neither
+    pub(crate) fn toggled(&self) -> Option<Element> {
+        match self.element_type {
+            ElementType::Switch => Some(match self.spark {
+                Spark::Head => Element { spark: Spark::Idle, color: SWITCH.color, ..*self },
+                _ => Element { spark: Spark::Head, color: SWITCH_ON_COLOR, ..*self },
+            }),
+            ElementType::Valve => {
+                let color = if self.open { VALVE.color } else { VALVE_OPEN_COLOR };
+                Some(Element { open: !self.open, color, ..*self })
+            }
+            _ => None,
+        }
+    }
+
+    // Push this cell along with the wind. Gases and fire drift a cell at a time, more
+    // often the stronger the wind, while liquids and light powders pick up speed.
+    pub(crate) fn blow(&self, grid: &mut Grid, x: usize, y: usize, wind_x: f32, wind_y: f32) {
+        let strength = (wind_x * wind_x + wind_y * wind_y).sqrt();
+        if strength == 0.0 {
+            return;
+        }
+        match self.element_type {
+            ElementType::Gas | ElementType::Fire => {
+                if thread_rng().gen::<f32>() >= strength {
+                    return;
+                }
+                let (dx, dy) = ((wind_x / strength).round() as isize, (wind_y / strength).round() as isize);
+                if let Some((new_x, new_y)) = neighbour(grid, x, y, dx, dy) {
+                    if grid.get(new_x, new_y) == NOTHING {
+                        grid.move_element(x, y, new_x, new_y);
+                    }
+                }
+            }
+            _ if self.is_liquid() || self.is_light_powder() => {
+                let mut blown = *self;
+                blown.velocity_x = (blown.velocity_x + wind_x).clamp(-TERMINAL_VELOCITY, TERMINAL_VELOCITY);
+                blown.velocity_y = (blown.velocity_y + wind_y).clamp(-TERMINAL_VELOCITY, TERMINAL_VELOCITY);
+                grid.set(x, y, blown);
+            }
+            _ => {}
+        }
+    }
+
+    // A portal linked to the other portals on the given channel
+    pub(crate) fn portal(channel: u8) -> Element {
+        Element {
+            channel,
+            color: PORTAL_COLORS[channel as usize % PORTAL_COLORS.len()],
+            ..PORTAL
+        }
+    }
+
+    // The channel of this portal, or None if this isn't one
+    pub(crate) fn portal_channel(&self) -> Option<u8> {
+        if self.element_type == ElementType::Portal {
+            Some(self.channel)
+        } else {
+            None
+        }
+    }
+
+    // This pipe, pump or valve moving liquid on with the given chance each tick, or
+    // None if this is something else
+    pub(crate) fn with_flow_rate(&self, rate: f32) -> Option<Element> {
+        match self.element_type {
+            ElementType::Pipe | ElementType::Pump | ElementType::Valve => Some(Element { flow_rate: rate, ..*self }),
+            _ => None,
+        }
+    }
+
+    // A generator emitting the given element in a direction, with the given chance per tick.
+    // Its colour is halfway between the faucet's and the element it emits.
+    pub(crate) fn generator(emits: &'static Element, emit_x: i8, emit_y: i8, rate: f32) -> Element {
+        Element {
+            element_type: ElementType::PixelGenerator,
+            color: mix(FAUCET.color, emits.color),
+            name: "Generator",
+            emits: Some(emits),
+            emit_x,
+            emit_y,
+            rate,
+            ..NOTHING
+        }
+    }
+
+    // Blow up, destroying or flinging everything within the blast radius. The force
+    // falls off with distance and blast-resistant elements soak some of it up.
+    // Explosives caught in the blast are detonated in turn.
+    pub(crate) fn explode(&self, grid: &mut Grid, x: usize, y: usize) {
+        if self.explosive <= 0.0 {
+            return;
+        }
+        let mut rng = thread_rng();
+        grid.set(x, y, FIRE);
+        let reach = self.explosive.ceil() as isize;
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                if distance == 0.0 || distance > self.explosive {
+                    continue;
+                }
+                let (nx, ny) = match neighbour(grid, x, y, dx, dy) {
+                    Some(position) => position,
+                    None => continue,
+                };
+                let force = 1.0 - distance / self.explosive;
+                let mut target = grid.get(nx, ny);
+                if target.explosive > 0.0 {
+                    grid.detonate(nx, ny);
+                } else if target == NOTHING {
+                    if rng.gen::<f32>() < force * 0.5 {
+                        grid.set(nx, ny, if rng.gen::<f32>() < force { FIRE } else { SMOKE });
+                    }
+                } else if rng.gen::<f32>() < force - target.blast_resistance {
+                    grid.set(nx, ny, if rng.gen::<f32>() < 0.5 { FIRE } else { SMOKE });
+                } else if target.is_movable() {
+                    // Survivors are thrown away from the centre
+                    let push = force * BLAST_SPEED / distance;
+                    target.velocity_x += dx as f32 * push;
+                    target.velocity_y += dy as f32 * push;
+                    grid.set(nx, ny, target);
+                }
+            }
+        }
+    }
+
+    // A drain that absorbs the given set of element types
+    pub(crate) fn drain(absorbs: u32) -> Element {
+        Element { absorbs, ..DRAIN }
+    }
+
+    pub(crate) fn absorbs(&self) -> u32 {
+        self.absorbs
+    }
+
+    // A sensor that switches on when any of the given set of element types is next to it
+    pub(crate) fn sensor(detects: u32) -> Element {
+        Element { detects, ..SENSOR }
+    }
+
+    pub(crate) fn detects(&self) -> u32 {
+        self.detects
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        self.name
+    }
+
+    // The built-in element with the same name as this one
+    pub(crate) fn species(&self) -> Option<&'static Element> {
+        ELEMENTS.iter().copied().find(|element| element.name == self.name)
+    }
+
+    // A live cell of the automaton registered under the given id
+    pub(crate) fn automaton(name: &'static str, color: Color, rule: u8) -> Element {
+        Element {
+            element_type: ElementType::Automaton,
+            color,
+            name,
+            rule,
+            ..NOTHING
+        }
+    }
+
+    // The id of the automaton this cell follows and how long it has been dying
+    pub(crate) fn automaton_state(&self) -> Option<(usize, u8)> {
+        if self.element_type == ElementType::Automaton {
+            Some((self.rule as usize, self.age))
+        } else {
+            None
+        }
+    }
+
+    // The next dying state of a Generations automaton cell, fading from the live
+    // cell's colour over its dying states
+    pub(crate) fn aged(&self, live: &Element, dying_states: u8) -> Element {
+        let mut next = *self;
+        next.age += 1;
+        let fade = (dying_states + 1 - next.age) as f32 / (dying_states + 1) as f32;
+        next.color = Color {
+            r: live.color.r * fade,
+            g: live.color.g * fade,
+            b: live.color.b * fade,
+        };
+        next
+    }
+}
 
-
 pub static SAND: Element = Element {
     element_type: ElementType::MoveableSolid,
     color: Color {
@@ -307,7 +1494,9 @@ pub static SAND: Element = Element {
         b: 0.0,
     },
     name: "Sand",
-    velocity_x: 0,
+    corrodibility: 0.2,
+    density: 1.6,
+    ..NOTHING
 };
 
 pub static WATER: Element = Element {
@@ -318,7 +1507,13 @@ pub static WATER: Element = Element {
         b: 92.0,
     },
     name: "Water",
-    velocity_x: 0,
+    melts_at: 100.0,
+    melts_into: Some(Phase(&STEAM)),
+    freezes_at: 0.0,
+    freezes_into: Some(Phase(&ICE)),
+    corrodibility: 0.05,
+    density: 1.0,
+    ..NOTHING
 };
 
 pub static STONE: Element = Element {
@@ -329,7 +1524,10 @@ pub static STONE: Element = Element {
         b: 169.0,
     },
     name: "Stone",
-    velocity_x: 0,
+    blast_resistance: 0.5,
+    corrodibility: 0.05,
+    melting: 0.002,
+    ..NOTHING
 };
 
 pub static NOTHING: Element = Element {
@@ -340,7 +1538,47 @@ pub static NOTHING: Element = Element {
         b: 0.0,
     },
     name: "Nothing",
-    velocity_x: 0,
+    velocity_x: 0.0,
+    velocity_y: 0.0,
+    offset_x: 0.0,
+    offset_y: 0.0,
+    rule: 0,
+    age: 0,
+    emits: None,
+    emit_x: 0,
+    emit_y: 0,
+    rate: 0.0,
+    absorbs: 0,
+    detects: 0,
+    density: 0.0,
+    flammability: 0.0,
+    decay: 0.0,
+    decays_into: None,
+    viscosity: 0.0,
+    melting: 0.0,
+    corrodibility: 0.0,
+    strength: 0,
+    explosive: 0.0,
+    blast_resistance: 0.0,
+    pull: 0.0,
+    conveys: 0,
+    contents: None,
+    flow_x: 0,
+    flow_y: 0,
+    channel: 0,
+    flow_rate: 0.0,
+    open: false,
+    temperature: AMBIENT_TEMPERATURE,
+    fixed_temperature: false,
+    conductivity: 1.0,
+    melts_at: 0.0,
+    melts_into: None,
+    freezes_at: 0.0,
+    freezes_into: None,
+    conductive: false,
+    spark: Spark::Idle,
+    logic: Logic::None,
+    history: [0, 0],
 };
 
 pub static MAGIC: Element = Element {
@@ -351,7 +1589,7 @@ pub static MAGIC: Element = Element {
         b: 0.0,
     },
     name: "Magic",
-    velocity_x: 0,
+    ..NOTHING
 };
 
 pub static FIRE: Element = Element {
@@ -362,29 +1600,36 @@ pub static FIRE: Element = Element {
         b: 0.0,
     },
     name: "Fire",
-    velocity_x: 0,
+    temperature: 800.0,
+    fixed_temperature: true,
+    ..NOTHING
 };
 
 pub static MAZE: Element = Element {
-    element_type: ElementType::Maze,
+    element_type: ElementType::Automaton,
     color: Color {
         r: 255.0,
         g: 255.0,
         b: 255.0,
     },
     name: "Maze",
-    velocity_x: 0,
+    corrodibility: 0.1,
+    ..NOTHING
 };
 
 pub static FAUCET: Element = Element {
-    element_type: ElementType::Faucet,
+    element_type: ElementType::PixelGenerator,
     color: Color {
         r: 150.0,
         g: 150.0,
         b: 150.0,
     },
     name: "Faucet",
-    velocity_x: 0,
+    emits: Some(&WATER),
+    emit_x: 0,
+    emit_y: 1,
+    rate: 1.0,
+    ..NOTHING
 };
 
 pub static DRAIN: Element = Element {
@@ -395,5 +1640,597 @@ pub static DRAIN: Element = Element {
         b: 50.0,
     },
     name: "Drain",
-    velocity_x: 0,
+    absorbs: LIQUIDS,
+    ..NOTHING
 };
+
+pub static OIL: Element = Element {
+    element_type: ElementType::Liquid,
+    color: Color {
+        r: 110.0,
+        g: 70.0,
+        b: 30.0,
+    },
+    name: "Oil",
+    corrodibility: 0.1,
+    density: 0.8,
+    flammability: 0.3,
+    ..NOTHING
+};
+
+pub static SMOKE: Element = Element {
+    element_type: ElementType::Gas,
+    color: Color {
+        r: 80.0,
+        g: 80.0,
+        b: 80.0,
+    },
+    name: "Smoke",
+    decay: 0.02,
+    ..NOTHING
+};
+
+pub static WOOD: Element = Element {
+    element_type: ElementType::ImmovableSolid,
+    color: Color {
+        r: 133.0,
+        g: 94.0,
+        b: 66.0,
+    },
+    name: "Wood",
+    blast_resistance: 0.2,
+    corrodibility: 0.2,
+    flammability: 0.05,
+    ..NOTHING
+};
+
+pub static PLANT: Element = Element {
+    element_type: ElementType::Plant,
+    color: Color {
+        r: 34.0,
+        g: 139.0,
+        b: 34.0,
+    },
+    name: "Plant",
+    corrodibility: 0.3,
+    flammability: 0.2,
+    ..NOTHING
+};
+
+pub static SEED: Element = Element {
+    element_type: ElementType::Seed,
+    color: Color {
+        r: 200.0,
+        g: 170.0,
+        b: 100.0,
+    },
+    name: "Seed",
+    corrodibility: 0.3,
+    density: 1.2,
+    flammability: 0.1,
+    ..NOTHING
+};
+
+pub static LAVA: Element = Element {
+    element_type: ElementType::Lava,
+    color: Color {
+        r: 255.0,
+        g: 100.0,
+        b: 0.0,
+    },
+    name: "Lava",
+    temperature: 1200.0,
+    fixed_temperature: true,
+    density: 2.5,
+    viscosity: 0.8,
+    // Slowly cools into stone
+    decay: 0.0005,
+    decays_into: Some(&STONE),
+    ..NOTHING
+};
+
+pub static OBSIDIAN: Element = Element {
+    element_type: ElementType::ImmovableSolid,
+    color: Color {
+        r: 40.0,
+        g: 20.0,
+        b: 50.0,
+    },
+    name: "Obsidian",
+    blast_resistance: 0.9,
+    corrodibility: 0.01,
+    ..NOTHING
+};
+
+pub static STEAM: Element = Element {
+    element_type: ElementType::Gas,
+    color: Color {
+        r: 200.0,
+        g: 200.0,
+        b: 220.0,
+    },
+    name: "Steam",
+    temperature: 110.0,
+    // Condenses back into water
+    decay: 0.01,
+    decays_into: Some(&WATER),
+    ..NOTHING
+};
+
+pub static ACID: Element = Element {
+    element_type: ElementType::Acid,
+    color: Color {
+        r: 180.0,
+        g: 255.0,
+        b: 60.0,
+    },
+    name: "Acid",
+    density: 1.1,
+    strength: 4,
+    ..NOTHING
+};
+
+pub static FUMES: Element = Element {
+    element_type: ElementType::Gas,
+    color: Color {
+        r: 150.0,
+        g: 200.0,
+        b: 100.0,
+    },
+    name: "Fumes",
+    decay: 0.03,
+    ..NOTHING
+};
+
+// Acid-proof
+pub static GLASS: Element = Element {
+    element_type: ElementType::ImmovableSolid,
+    color: Color {
+        r: 170.0,
+        g: 220.0,
+        b: 230.0,
+    },
+    name: "Glass",
+    blast_resistance: 0.2,
+    ..NOTHING
+};
+
+pub static GUNPOWDER: Element = Element {
+    element_type: ElementType::MoveableSolid,
+    color: Color {
+        r: 90.0,
+        g: 70.0,
+        b: 80.0,
+    },
+    name: "Gunpowder",
+    density: 1.4,
+    corrodibility: 0.2,
+    flammability: 0.8,
+    explosive: 4.0,
+    ..NOTHING
+};
+
+pub static C4: Element = Element {
+    element_type: ElementType::ImmovableSolid,
+    color: Color {
+        r: 230.0,
+        g: 230.0,
+        b: 200.0,
+    },
+    name: "C4",
+    corrodibility: 0.1,
+    flammability: 0.5,
+    explosive: 10.0,
+    ..NOTHING
+};
+
+pub static ICE: Element = Element {
+    element_type: ElementType::ImmovableSolid,
+    color: Color {
+        r: 180.0,
+        g: 230.0,
+        b: 255.0,
+    },
+    name: "Ice",
+    corrodibility: 0.1,
+    temperature: -10.0,
+    melts_at: 0.0,
+    melts_into: Some(Phase(&WATER)),
+    ..NOTHING
+};
+
+// A light powder that piles up and packs into ice
+pub static SNOW: Element = Element {
+    element_type: ElementType::Snow,
+    color: Color {
+        r: 240.0,
+        g: 250.0,
+        b: 255.0,
+    },
+    name: "Snow",
+    density: 0.3,
+    corrodibility: 0.3,
+    temperature: -5.0,
+    conductivity: 0.05,
+    melts_at: 0.0,
+    melts_into: Some(Phase(&WATER)),
+    ..NOTHING
+};
+
+// Stays freezing cold, freezing nearby water
+pub static CRYO: Element = Element {
+    element_type: ElementType::ImmovableSolid,
+    color: Color {
+        r: 0.0,
+        g: 120.0,
+        b: 255.0,
+    },
+    name: "Cryo",
+    temperature: -100.0,
+    fixed_temperature: true,
+    ..NOTHING
+};
+
+// Carries sparks from batteries along wires
+pub static METAL: Element = Element {
+    element_type: ElementType::ImmovableSolid,
+    color: Color {
+        r: 150.0,
+        g: 150.0,
+        b: 160.0,
+    },
+    name: "Metal",
+    corrodibility: 0.05,
+    blast_resistance: 0.7,
+    conductive: true,
+    ..NOTHING
+};
+
+// Sends a spark into the metal touching it every few ticks
+pub static BATTERY: Element = Element {
+    element_type: ElementType::Battery,
+    color: Color {
+        r: 200.0,
+        g: 40.0,
+        b: 40.0,
+    },
+    name: "Battery",
+    corrodibility: 0.05,
+    blast_resistance: 0.7,
+    ..NOTHING
+};
+
+// Logic gates: input A comes from the left, input B from above or below, and the
+// output sparks whatever is to the right
+pub static NOT_GATE: Element = Element {
+    element_type: ElementType::Gate,
+    color: Color {
+        r: 200.0,
+        g: 100.0,
+        b: 200.0,
+    },
+    name: "Not gate",
+    logic: Logic::Not,
+    corrodibility: 0.05,
+    blast_resistance: 0.7,
+    ..NOTHING
+};
+
+pub static AND_GATE: Element = Element {
+    element_type: ElementType::Gate,
+    color: Color {
+        r: 100.0,
+        g: 200.0,
+        b: 200.0,
+    },
+    name: "And gate",
+    logic: Logic::And,
+    corrodibility: 0.05,
+    blast_resistance: 0.7,
+    ..NOTHING
+};
+
+pub static OR_GATE: Element = Element {
+    element_type: ElementType::Gate,
+    color: Color {
+        r: 200.0,
+        g: 200.0,
+        b: 100.0,
+    },
+    name: "Or gate",
+    logic: Logic::Or,
+    corrodibility: 0.05,
+    blast_resistance: 0.7,
+    ..NOTHING
+};
+
+pub static XOR_GATE: Element = Element {
+    element_type: ElementType::Gate,
+    color: Color {
+        r: 200.0,
+        g: 130.0,
+        b: 60.0,
+    },
+    name: "Xor gate",
+    logic: Logic::Xor,
+    corrodibility: 0.05,
+    blast_resistance: 0.7,
+    ..NOTHING
+};
+
+
+// Passes sparks from the left on to the right after a delay
+pub static DELAY: Element = Element {
+    element_type: ElementType::Gate,
+    color: Color {
+        r: 120.0,
+        g: 120.0,
+        b: 200.0,
+    },
+    name: "Delay line",
+    logic: Logic::Delay,
+    corrodibility: 0.05,
+    blast_resistance: 0.7,
+    ..NOTHING
+};
+
+// Powers the cells around it while switched on, see Grid::toggle_switch
+pub static SWITCH: Element = Element {
+    element_type: ElementType::Switch,
+    color: Color {
+        r: 30.0,
+        g: 100.0,
+        b: 30.0,
+    },
+    name: "Switch",
+    corrodibility: 0.05,
+    blast_resistance: 0.7,
+    ..NOTHING
+};
+
+// Switches on while liquid (or whatever else it is set to detect) is next to it
+pub static SENSOR: Element = Element {
+    element_type: ElementType::Sensor,
+    color: Color {
+        r: 120.0,
+        g: 40.0,
+        b: 100.0,
+    },
+    name: "Sensor",
+    detects: LIQUIDS,
+    corrodibility: 0.05,
+    blast_resistance: 0.7,
+    ..NOTHING
+};
+
+// Copies whatever first touches it into the empty cells around it. Its colour is
+// halfway between this and the element it copies once it has learned one.
+pub static CLONE: Element = Element {
+    element_type: ElementType::Clone,
+    color: Color {
+        r: 220.0,
+        g: 200.0,
+        b: 60.0,
+    },
+    name: "Clone",
+    corrodibility: 0.05,
+    blast_resistance: 0.7,
+    ..NOTHING
+};
+
+// Deletes anything that touches it
+pub static VOID: Element = Element {
+    element_type: ElementType::Void,
+    color: Color {
+        r: 20.0,
+        g: 0.0,
+        b: 30.0,
+    },
+    name: "Void",
+    blast_resistance: 1.0,
+    ..NOTHING
+};
+
+// A void that also pulls in movable particles from around it
+pub static ATTRACTOR: Element = Element {
+    color: Color {
+        r: 70.0,
+        g: 0.0,
+        b: 110.0,
+    },
+    name: "Attractor",
+    pull: 12.0,
+    ..VOID
+};
+
+// Carry whatever sits on them one cell per tick
+pub static CONVEYOR_LEFT: Element = Element {
+    element_type: ElementType::Conveyor,
+    color: Color {
+        r: 60.0,
+        g: 60.0,
+        b: 90.0,
+    },
+    name: "Left conveyor",
+    conveys: -1,
+    corrodibility: 0.05,
+    blast_resistance: 0.7,
+    ..NOTHING
+};
+
+pub static CONVEYOR_RIGHT: Element = Element {
+    color: Color {
+        r: 90.0,
+        g: 60.0,
+        b: 60.0,
+    },
+    name: "Right conveyor",
+    conveys: 1,
+    ..CONVEYOR_LEFT
+};
+
+// Carries liquid along the pipes connected to it
+pub static PIPE: Element = Element {
+    element_type: ElementType::Pipe,
+    color: Color {
+        r: 100.0,
+        g: 110.0,
+        b: 100.0,
+    },
+    name: "Pipe",
+    flow_rate: 1.0,
+    corrodibility: 0.05,
+    blast_resistance: 0.7,
+    ..NOTHING
+};
+
+// Pulls liquid from next to it into the pipe next to it
+pub static PUMP: Element = Element {
+    element_type: ElementType::Pump,
+    color: Color {
+        r: 40.0,
+        g: 90.0,
+        b: 140.0,
+    },
+    name: "Pump",
+    ..PIPE
+};
+
+// A pipe that only lets liquid through while it is open, see Element::is_open
+pub static VALVE: Element = Element {
+    element_type: ElementType::Valve,
+    color: Color {
+        r: 140.0,
+        g: 60.0,
+        b: 60.0,
+    },
+    name: "Valve",
+    ..PIPE
+};
+
+// Particles going into a portal come out of another portal on the same channel,
+// see Element::portal
+pub static PORTAL: Element = Element {
+    element_type: ElementType::Portal,
+    color: PORTAL_COLORS[0],
+    name: "Portal",
+    blast_resistance: 1.0,
+    ..NOTHING
+};
+
+// Every built-in element, for looking elements up by name
+pub static ELEMENTS: [&Element; 43] = [
+    &SAND, &WATER, &STONE, &NOTHING, &MAGIC, &FIRE, &MAZE, &FAUCET, &DRAIN, &OIL, &SMOKE,
+    &WOOD, &PLANT, &SEED, &LAVA, &OBSIDIAN, &STEAM, &ACID, &FUMES, &GLASS, &GUNPOWDER, &C4,
+    &ICE, &SNOW, &CRYO, &METAL, &BATTERY, &NOT_GATE, &AND_GATE, &OR_GATE, &XOR_GATE, &DELAY,
+    &SWITCH, &SENSOR, &CLONE, &VOID, &ATTRACTOR, &CONVEYOR_LEFT, &CONVEYOR_RIGHT, &PIPE,
+    &PUMP, &VALVE, &PORTAL,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lava_sinks_through_lighter_liquids() {
+        assert!(LAVA.can_displace(&OIL));
+        assert!(LAVA.can_displace(&WATER));
+        assert!(!OIL.can_displace(&LAVA));
+        assert!(!SAND.can_displace(&LAVA));
+    }
+
+    #[test]
+    fn acid_sinks_and_powders_sink_through_it() {
+        assert!(ACID.can_displace(&WATER));
+        assert!(ACID.can_displace(&OIL));
+        assert!(!WATER.can_displace(&ACID));
+        assert!(SAND.can_displace(&ACID));
+        assert!(GUNPOWDER.can_displace(&ACID));
+    }
+
+    #[test]
+    fn plain_drains_and_sensors_take_lava_and_acid() {
+        for liquid in [&WATER, &OIL, &LAVA, &ACID] {
+            assert_ne!(DRAIN.absorbs() & liquid.element_type.mask(), 0);
+            assert_ne!(SENSOR.detects() & liquid.element_type.mask(), 0);
+        }
+    }
+
+    #[test]
+    fn water_ice_and_steam_change_into_each_other() {
+        let changes = |element: &Element, temperature: f32| Element { temperature, ..*element }.phase_change();
+        assert!(changes(&WATER, -10.0).is_some_and(|into| std::ptr::eq(into, &ICE)));
+        assert!(changes(&WATER, 150.0).is_some_and(|into| std::ptr::eq(into, &STEAM)));
+        assert!(changes(&ICE, 10.0).is_some_and(|into| std::ptr::eq(into, &WATER)));
+        assert!(changes(&WATER, 20.0).is_none());
+        assert!(WATER == WATER && ICE != WATER);
+    }
+
+    // Opening a valve by hand lets liquid through without sparking the wires around it
+    #[test]
+    fn valves_open_and_close_without_powering() {
+        let open = VALVE.toggled().unwrap();
+        assert!(open.open);
+        assert!(ADJACENT.iter().all(|&(dx, dy)| !open.powers(dx, dy)));
+        assert!(open.toggled().is_some_and(|closed| closed == VALVE));
+    }
+
+    // Wire a gate's inputs to batteries (or leave them unconnected) and return the ticks
+    // on which the first cell of its output wire sparks
+    fn output_sparks(gate: &Element, a: bool, b: bool, ticks: usize) -> Vec<usize> {
+        let mut grid = Grid::new(12, 12);
+        let (x, y) = (5, 6);
+        grid.set(x, y, *gate);
+        for i in 1..=3 {
+            grid.set(x + i, y, METAL);
+        }
+        if a {
+            grid.set(x - 3, y, BATTERY);
+            grid.set(x - 2, y, METAL);
+            grid.set(x - 1, y, METAL);
+        }
+        if b {
+            grid.set(x, y - 3, BATTERY);
+            grid.set(x, y - 2, METAL);
+            grid.set(x, y - 1, METAL);
+        }
+        let mut sparks = Vec::new();
+        for tick in 1..=ticks {
+            grid.update();
+            if grid.get(x + 1, y).spark == Spark::Head {
+                sparks.push(tick);
+            }
+        }
+        sparks
+    }
+
+    // Whether a gate's output keeps sparking once its inputs have settled
+    fn gate_output(gate: &Element, a: bool, b: bool) -> bool {
+        output_sparks(gate, a, b, 40).iter().any(|&tick| tick > 10)
+    }
+
+    #[test]
+    fn gates_follow_their_truth_tables() {
+        let inputs = [(false, false), (false, true), (true, false), (true, true)];
+        for (gate, table) in [
+            (&NOT_GATE, [true, true, false, false]),
+            (&AND_GATE, [false, false, false, true]),
+            (&OR_GATE, [false, true, true, true]),
+            (&XOR_GATE, [false, true, true, false]),
+        ] {
+            for ((a, b), expected) in inputs.into_iter().zip(table) {
+                assert_eq!(gate_output(gate, a, b), expected, "{} with A {} and B {}", gate.name, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn delay_lines_pass_sparks_on_late() {
+        assert!(output_sparks(&DELAY, false, false, 40).is_empty());
+        assert!(output_sparks(&DELAY, false, true, 40).is_empty());
+        let sparks = output_sparks(&DELAY, true, false, 40);
+        let first = sparks.first().copied().unwrap_or(0);
+        assert!(first > DELAY_TICKS as usize, "the first spark came out on tick {}", first);
+        assert!(sparks.iter().any(|&tick| tick > 30));
+    }
+}
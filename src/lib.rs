@@ -1,14 +1,27 @@
 use element::*;
+use std::collections::{HashMap, HashSet};
 use wasm_bindgen::JsValue;
 // use wasm_bindgen::prelude::*;
 // use wasm_bindgen::JsCast;
 use web_sys::CanvasRenderingContext2d;
 use wasm_bindgen::prelude::wasm_bindgen;
 use web_sys::console;
+use chunk::CHUNK_SIZE;
+mod automaton;
+mod chunk;
 mod element;
 
 pub const GRID_WIDTH: usize = 226;
 pub const GRID_HEIGHT: usize = 126;
+// Most cells a grid can be resized or rescaled to. Every cell holds a whole element,
+// a couple of hundred bytes, so this keeps the grid to around 100MB.
+pub const MAX_CELLS: usize = 1 << 19;
+// Speed the painted wind field adds to what it blows each tick, in cells per tick
+const WIND_SPEED: f32 = 0.5;
+// Speed a gust adds to what it blows, once
+const GUST_SPEED: f32 = 4.0;
+// Empty cells with wind blowing through them are tinted this colour
+const WIND_COLOR: Color = Color { r: 20.0, g: 30.0, b: 45.0 };
 // #[wasm_bindgen]
 // #[derive(Clone, Copy, PartialEq)]
 // pub struct Vector2 {
@@ -24,6 +37,26 @@ pub const GRID_HEIGHT: usize = 126;
 //     }
 // }
 
+// Which part of the existing content stays in place when the grid is resized
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    Centre,
+    Bottom,
+}
+
+// What dragging the brush does with wind instead of painting elements
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum WindBrush {
+    Off,
+    // Paint a wind field that keeps blowing along the drag direction
+    Field,
+    // Blow a single gust along the drag direction
+    Gust,
+}
+
 #[wasm_bindgen]
 pub struct Grid {
     width: usize,
@@ -33,6 +66,44 @@ pub struct Grid {
     previous_mouse_x: usize,
     previous_mouse_y: usize,
     brush_size: usize,
+    // Whether the brush paints generators of the selected element, and how they emit
+    generator_brush: bool,
+    generator_x: i8,
+    generator_y: i8,
+    generator_rate: f32,
+    // The set of element types drains placed by the brush absorb
+    drain_absorbs: u32,
+    // How many of each element drains have absorbed, by element name
+    drained: HashMap<&'static str, u32>,
+    // The set of element types sensors placed by the brush detect
+    sensor_detects: u32,
+    // Chance each tick that pipes, pumps and valves placed by the brush move liquid on
+    pipe_rate: f32,
+    // Channel of the portals placed by the brush
+    portal_channel: u8,
+    // The cells of the portals on each channel, by index, kept up to date by set
+    portals: HashMap<u8, HashSet<usize>>,
+    wind_brush: WindBrush,
+    // The painted wind field: which way the wind blows through each windy cell, by index
+    wind: HashMap<usize, (f32, f32)>,
+    // Sensors that switched on since JS last asked, see take_sensor_events
+    sensor_events: Vec<(usize, usize)>,
+    chunks: Vec<chunk::Chunk>,
+    chunks_wide: usize,
+    // Tick on which each cell was last written, so particles are only moved once per tick
+    stamps: Vec<u32>,
+    tick: u32,
+    // Acceleration applied to falling particles each tick
+    gravity_x: f32,
+    gravity_y: f32,
+    // Cells that change in the next automaton or electrical generation, applied all at once
+    generation: Vec<(usize, usize, element::Element)>,
+    // Automaton elements, indexed by the rule id stored in their cells. Maze is always first.
+    automata: Vec<automaton::Automaton>,
+    // Explosives that have been set off and will blow up at the end of this tick
+    explosions: Vec<(usize, usize)>,
+    // Attractors that pull particles in at the end of this tick
+    attractors: Vec<(usize, usize)>,
 }
 
 #[wasm_bindgen]
@@ -41,16 +112,61 @@ impl Grid {
 
     #[wasm_bindgen(constructor)]
     pub fn new(width: usize, height: usize) -> Grid {
-        Grid {
+        let (width, height) = (width.max(1), height.max(1));
+        let mut grid = Grid {
             width,
             height,
             elements: vec![element::NOTHING; width * height],
             selected_element: element::WATER,
             previous_mouse_x: 0,
             previous_mouse_y: 0,
-            brush_size: 3
-        }
+            brush_size: 3,
+            generator_brush: false,
+            generator_x: 0,
+            generator_y: 1,
+            generator_rate: 1.0,
+            drain_absorbs: element::DRAIN.absorbs(),
+            drained: HashMap::new(),
+            sensor_detects: element::SENSOR.detects(),
+            sensor_events: Vec::new(),
+            pipe_rate: 1.0,
+            portal_channel: 0,
+            portals: HashMap::new(),
+            wind_brush: WindBrush::Off,
+            wind: HashMap::new(),
+            chunks: Vec::new(),
+            chunks_wide: 0,
+            stamps: vec![0; width * height],
+            tick: 0,
+            gravity_x: 0.0,
+            gravity_y: element::GRAVITY,
+            generation: Vec::new(),
+            automata: vec![automaton::Automaton {
+                rule: automaton::Rule::MAZE,
+                element: &element::MAZE,
+            }],
+            explosions: Vec::new(),
+            attractors: Vec::new(),
+        };
+        grid.build_chunks();
+        grid
+    }
+
+    // Create a grid using the default GRID_WIDTH and GRID_HEIGHT
+    pub fn with_default_size() -> Grid {
+        Grid::new(GRID_WIDTH, GRID_HEIGHT)
     }
+
+    #[wasm_bindgen]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[wasm_bindgen]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
     // Get the element at the given position
     pub fn get(&self, x: usize, y: usize) -> element::Element {
         if x < self.width && y < self.height {
@@ -67,10 +183,91 @@ impl Grid {
 
     pub fn set(&mut self, x: usize, y: usize, value: element::Element) {
         if x < self.width && y < self.height {
-            self.elements[y * self.width + x] = value;
+            let index = y * self.width + x;
+            let old = self.elements[index];
+            if old != value {
+                if old.portal_channel() != value.portal_channel() {
+                    self.index_portal(index, &old, &value);
+                }
+                self.elements[index] = value;
+                self.stamps[index] = self.tick;
+                self.wake_around(x, y);
+            }
+        }
+    }
+
+    // Move the cell at the given index between channels in the portal index
+    fn index_portal(&mut self, index: usize, old: &element::Element, new: &element::Element) {
+        if let Some(channel) = old.portal_channel() {
+            if let Some(cells) = self.portals.get_mut(&channel) {
+                cells.remove(&index);
+            }
+        }
+        if let Some(channel) = new.portal_channel() {
+            self.portals.entry(channel).or_default().insert(index);
+        }
+    }
+
+    // Rebuild the portal index from scratch after the whole grid has been replaced
+    fn index_portals(&mut self) {
+        self.portals.clear();
+        for index in 0..self.elements.len() {
+            let cell = self.elements[index];
+            self.index_portal(index, &element::NOTHING, &cell);
+        }
+    }
+
+    // Whether the cell at the given position was written this tick, e.g. by a particle
+    // moving into it
+    fn moved_this_tick(&self, x: usize, y: usize) -> bool {
+        self.stamps[y * self.width + x] == self.tick
+    }
+
+    // Split the grid into chunks, all of them awake
+    fn build_chunks(&mut self) {
+        self.chunks_wide = self.width.div_ceil(CHUNK_SIZE);
+        let chunks_high = self.height.div_ceil(CHUNK_SIZE);
+        self.chunks = vec![chunk::Chunk::new(); self.chunks_wide * chunks_high];
+        self.wake_all();
+    }
+
+    // Make sure the cell at the given position gets updated next tick
+    fn wake(&mut self, x: usize, y: usize) {
+        let index = (y / CHUNK_SIZE) * self.chunks_wide + x / CHUNK_SIZE;
+        self.chunks[index].wake(x, y);
+    }
+
+    // Wake a changed cell and its neighbours, which may sit in neighbouring chunks
+    fn wake_around(&mut self, x: usize, y: usize) {
+        for ny in y.saturating_sub(1)..=(y + 1).min(self.height - 1) {
+            for nx in x.saturating_sub(1)..=(x + 1).min(self.width - 1) {
+                self.wake(nx, ny);
+            }
+        }
+    }
+
+    // Wake every chunk so the whole grid is updated next tick
+    #[wasm_bindgen]
+    pub fn wake_all(&mut self) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+        for chunk_y in 0..self.chunks.len() / self.chunks_wide {
+            for chunk_x in 0..self.chunks_wide {
+                let min_x = chunk_x * CHUNK_SIZE;
+                let min_y = chunk_y * CHUNK_SIZE;
+                self.wake(min_x, min_y);
+                self.wake((min_x + CHUNK_SIZE).min(self.width) - 1, (min_y + CHUNK_SIZE).min(self.height) - 1);
+            }
         }
     }
 
+    // Number of chunks that will be updated this tick
+    #[wasm_bindgen]
+    pub fn awake_chunks(&self) -> usize {
+        self.chunks.iter().filter(|chunk| !chunk.is_sleeping()).count()
+    }
+
     // Move the element at the given position to the new position   
     pub fn move_element(&mut self, x: usize, y: usize, new_x:usize, new_y: usize ) {
         let element = self.get(x, y);
@@ -86,14 +283,498 @@ impl Grid {
         self.set(new_x, new_y, element1);
     }
 
-    // Update the grid
+    // Change the direction and strength of gravity, e.g. (0, -0.4) to fall upwards
+    // or (0, 0) for zero-g
+    #[wasm_bindgen]
+    pub fn set_gravity(&mut self, gravity_x: f32, gravity_y: f32) {
+        self.gravity_x = gravity_x;
+        self.gravity_y = gravity_y;
+        self.wake_all();
+    }
+
+    // The grid direction gravity mostly pulls in, or (0, 0) without gravity
+    fn down(&self) -> (isize, isize) {
+        if self.gravity_x == 0.0 && self.gravity_y == 0.0 {
+            (0, 0)
+        } else if self.gravity_y.abs() >= self.gravity_x.abs() {
+            (0, self.gravity_y.signum() as isize)
+        } else {
+            (self.gravity_x.signum() as isize, 0)
+        }
+    }
+
+    // The direction at right angles to gravity that particles spread along
+    fn side(&self) -> (isize, isize) {
+        let (down_x, down_y) = self.down();
+        (-down_y, down_x)
+    }
+
+    // Update the grid, skipping chunks where nothing changed last tick.
+    // The grid is scanned in lines across gravity (rows, or columns when gravity pulls
+    // sideways), starting with the line closest to where gravity pulls. Lines alternate
+    // between scanning forwards and backwards (flipping every tick as well) so that
+    // neither side is favoured when particles spread.
     pub fn update(&mut self) {
-        for y in (0..self.height).rev() {
-            for x in 0..self.width {
-                let mut element = self.get( x, y );
-                element.step(self, x, y);
+        self.tick = self.tick.wrapping_add(1);
+        for chunk in self.chunks.iter_mut() {
+            chunk.advance();
+        }
+        let sideways = self.down().0 != 0;
+        let (lines, chunks_along) = if sideways {
+            (self.width, self.chunks.len() / self.chunks_wide)
+        } else {
+            (self.height, self.chunks_wide)
+        };
+        let pulled_to_start = if sideways { self.gravity_x < 0.0 } else { self.gravity_y < 0.0 };
+        for i in 0..lines {
+            let line = if pulled_to_start { i } else { lines - 1 - i };
+            let forwards = (line as u32).wrapping_add(self.tick).is_multiple_of(2);
+            for j in 0..chunks_along {
+                let chunk_along = if forwards { j } else { chunks_along - 1 - j };
+                let index = if sideways {
+                    chunk_along * self.chunks_wide + line / CHUNK_SIZE
+                } else {
+                    (line / CHUNK_SIZE) * self.chunks_wide + chunk_along
+                };
+                let rect = match self.chunks[index].dirty {
+                    Some(rect) if sideways && rect.contains_column(line) => rect,
+                    Some(rect) if !sideways && rect.contains_row(line) => rect,
+                    _ => continue,
+                };
+                let (min, max) = if sideways { (rect.min_y, rect.max_y) } else { (rect.min_x, rect.max_x) };
+                for k in 0..=(max - min) {
+                    let along = if forwards { min + k } else { max - k };
+                    let (x, y) = if sideways { (line, along) } else { (along, line) };
+                    // Skip particles that already moved here this tick
+                    if self.moved_this_tick(x, y) {
+                        continue;
+                    }
+                    let mut element = self.get( x, y );
+                    element.step(self, x, y);
+                }
             }
         }
+        self.step_pressure();
+        self.step_wind();
+        self.step_heat();
+        self.step_electricity();
+        self.step_explosions();
+        self.step_attractors();
+        self.step_automata();
+    }
+
+    // Even out the levels of liquid in connected vessels, see element::equalise_levels
+    fn step_pressure(&mut self) {
+        let mut visited = HashSet::new();
+        for index in 0..self.chunks.len() {
+            let rect = match self.chunks[index].dirty {
+                Some(rect) => rect,
+                None => continue,
+            };
+            for y in rect.min_y..=rect.max_y {
+                for x in rect.min_x..=rect.max_x {
+                    element::equalise_levels(self, x, y, &mut visited);
+                }
+            }
+        }
+    }
+
+    // Blow whatever is in the painted wind field along
+    fn step_wind(&mut self) {
+        let wind = std::mem::take(&mut self.wind);
+        for (&index, &(wind_x, wind_y)) in wind.iter() {
+            let (x, y) = (index % self.width, index / self.width);
+            let cell = self.get(x, y);
+            cell.blow(self, x, y, wind_x, wind_y);
+        }
+        self.wind = wind;
+    }
+
+    // Conduct heat between touching cells in the chunks that are awake
+    fn step_heat(&mut self) {
+        for index in 0..self.chunks.len() {
+            let rect = match self.chunks[index].dirty {
+                Some(rect) => rect,
+                None => continue,
+            };
+            for y in rect.min_y..=rect.max_y {
+                for x in rect.min_x..=rect.max_x {
+                    element::conduct_heat(self, x, y);
+                }
+            }
+        }
+    }
+
+    // Move sparks along conductors. Like the automata, every change is worked out
+    // before any is applied so a spark moves exactly one cell per tick.
+    fn step_electricity(&mut self) {
+        let mut generation = std::mem::take(&mut self.generation);
+        for chunk in self.chunks.iter() {
+            let rect = match chunk.dirty {
+                Some(rect) => rect,
+                None => continue,
+            };
+            for y in rect.min_y..=rect.max_y {
+                for x in rect.min_x..=rect.max_x {
+                    if let Some(next) = element::next_charge(self, x, y) {
+                        generation.push((x, y, next));
+                    }
+                }
+            }
+        }
+        for (x, y, cell) in generation.drain(..) {
+            self.set(x, y, cell);
+            cell.discharge(self, x, y);
+        }
+        self.generation = generation;
+    }
+
+    // Flip the switch or valve at the given position on or off
+    #[wasm_bindgen]
+    pub fn toggle_switch(&mut self, x: usize, y: usize) {
+        if !self.is_within_bounds(x, y) {
+            return;
+        }
+        if let Some(switch) = self.get(x, y).toggled() {
+            self.set(x, y, switch);
+        }
+    }
+
+    // Set off the explosive at the given position at the end of this tick
+    fn detonate(&mut self, x: usize, y: usize) {
+        self.explosions.push((x, y));
+    }
+
+    // Blow up everything that was detonated, including explosives caught in the blasts.
+    // Each explosive is replaced by fire when it blows up, so it only goes off once.
+    fn step_explosions(&mut self) {
+        while let Some((x, y)) = self.explosions.pop() {
+            let explosive = self.get(x, y);
+            explosive.explode(self, x, y);
+        }
+    }
+
+    // Pull particles in around the given attractor at the end of this tick
+    fn attract(&mut self, x: usize, y: usize) {
+        self.attractors.push((x, y));
+    }
+
+    // Pull particles towards every attractor that stepped this tick. This happens after
+    // the scan, otherwise the particles it pulls would be marked as moved and skipped.
+    fn step_attractors(&mut self) {
+        while let Some((x, y)) = self.attractors.pop() {
+            let attractor = self.get(x, y);
+            attractor.pull_in(self, x, y);
+        }
+    }
+
+    // Evaluate automaton cells (Maze and any registered with register_automaton). Every
+    // change is worked out from the current grid before any is applied, so births and
+    // deaths can't cascade within a generation or depend on the scan order.
+    fn step_automata(&mut self) {
+        let mut generation = std::mem::take(&mut self.generation);
+        for chunk in self.chunks.iter() {
+            let rect = match chunk.dirty {
+                Some(rect) => rect,
+                None => continue,
+            };
+            for y in rect.min_y..=rect.max_y {
+                for x in rect.min_x..=rect.max_x {
+                    if let Some(next) = self.next_generation(x, y) {
+                        generation.push((x, y, next));
+                    }
+                }
+            }
+        }
+        for (x, y, cell) in generation.drain(..) {
+            self.set(x, y, cell);
+        }
+        self.generation = generation;
+    }
+
+    // What the cell at the given position becomes in the next automaton generation,
+    // or None if it stays the same
+    fn next_generation(&self, x: usize, y: usize) -> Option<element::Element> {
+        let cell = self.get(x, y);
+        if let Some((id, age)) = cell.automaton_state() {
+            let automaton = &self.automata[id];
+            let dying_states = automaton.rule.dying_states();
+            let dies = if age > 0 {
+                true
+            } else {
+                let neighbours = self.live_neighbours(x, y);
+                !automaton.rule.survives(neighbours.iter().filter(|&&n| n == id).count())
+            };
+            if !dies {
+                return None;
+            }
+            if age < dying_states {
+                return Some(cell.aged(automaton.element, dying_states));
+            }
+            return Some(element::NOTHING);
+        }
+        if cell != element::NOTHING {
+            return None;
+        }
+        // An empty cell is born into the first automaton whose birth rule matches
+        let neighbours = self.live_neighbours(x, y);
+        let mut ids = neighbours.clone();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.into_iter()
+            .find(|&id| self.automata[id].rule.is_born(neighbours.iter().filter(|&&n| n == id).count()))
+            .map(|id| *self.automata[id].element)
+    }
+
+    // The automaton ids of the live automaton cells around the given position
+    fn live_neighbours(&self, x: usize, y: usize) -> Vec<usize> {
+        let mut ids = Vec::new();
+        for ny in y.saturating_sub(1)..=(y + 1).min(self.height - 1) {
+            for nx in x.saturating_sub(1)..=(x + 1).min(self.width - 1) {
+                if (nx, ny) == (x, y) {
+                    continue;
+                }
+                if let Some((id, 0)) = self.get(nx, ny).automaton_state() {
+                    ids.push(id);
+                }
+            }
+        }
+        ids
+    }
+
+    // Register a new automaton element from a rulestring such as "B3/S23" (Life),
+    // "B36/S23" (HighLife) or "/2/3" (Brian's Brain) and return its id. The name has to
+    // be new, since elements are told apart by name.
+    #[wasm_bindgen]
+    pub fn register_automaton(&mut self, name: &str, rule: &str, r: f32, g: f32, b: f32) -> Result<usize, JsValue> {
+        let rule = automaton::Rule::parse(rule).map_err(|error| JsValue::from_str(&error))?;
+        self.check_automaton_name(name).map_err(|error| JsValue::from_str(&error))?;
+        let id = self.automata.len();
+        if id > u8::MAX as usize {
+            return Err(JsValue::from_str("Too many automata registered"));
+        }
+        // Elements are 'static so generators can refer to them; registrations are rare
+        // and live as long as the page anyway
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let element = Box::leak(Box::new(element::Element::automaton(name, element::Color { r, g, b }, id as u8)));
+        self.automata.push(automaton::Automaton { rule, element });
+        Ok(id)
+    }
+
+    // Make sure a new automaton's name isn't empty or already used by another element
+    fn check_automaton_name(&self, name: &str) -> Result<(), String> {
+        if name.trim().is_empty() {
+            return Err("Automata need a name".to_string());
+        }
+        let taken = element::ELEMENTS.iter().any(|element| element.name() == name)
+            || self.automata.iter().any(|automaton| automaton.element.name() == name);
+        if taken {
+            return Err(format!("There is already an element called \"{}\"", name));
+        }
+        Ok(())
+    }
+
+    // Paint with a registered automaton element
+    #[wasm_bindgen]
+    pub fn select_automaton(&mut self, id: usize) {
+        if let Some(automaton) = self.automata.get(id) {
+            self.selected_element = *automaton.element;
+        }
+    }
+
+    // The pristine built-in or registered element a cell is an instance of
+    fn species(&self, cell: &element::Element) -> Option<&'static element::Element> {
+        match cell.automaton_state() {
+            Some((id, _)) => Some(self.automata[id].element),
+            None => cell.species(),
+        }
+    }
+
+    // Set which way generators placed by the generator brush emit, and their chance
+    // of emitting each tick
+    #[wasm_bindgen]
+    pub fn set_generator(&mut self, direction_x: i8, direction_y: i8, rate: f32) {
+        self.generator_x = direction_x.signum();
+        self.generator_y = direction_y.signum();
+        self.generator_rate = rate.clamp(0.0, 1.0);
+    }
+
+    // Set which element types drains placed by the brush absorb, as a combination of
+    // 1 << ElementType values. Pass 0 to go back to drains that absorb liquids only.
+    #[wasm_bindgen]
+    pub fn set_drain_absorbs(&mut self, mask: u32) {
+        self.drain_absorbs = if mask == 0 { element::DRAIN.absorbs() } else { mask };
+    }
+
+    // Set drains placed by the brush to absorb everything
+    #[wasm_bindgen]
+    pub fn set_drain_absorbs_all(&mut self) {
+        self.drain_absorbs = element::ABSORB_ALL;
+    }
+
+    fn count_drained(&mut self, element: &element::Element) {
+        let name = self.species(element).map_or("Unknown", |species| species.name());
+        *self.drained.entry(name).or_insert(0) += 1;
+    }
+
+    // How many cells of the named element drains have absorbed since the last reset
+    #[wasm_bindgen]
+    pub fn drained_count(&self, name: &str) -> u32 {
+        self.drained.get(name).copied().unwrap_or(0)
+    }
+
+    // How many cells of any element drains have absorbed since the last reset
+    #[wasm_bindgen]
+    pub fn drained_total(&self) -> u32 {
+        self.drained.values().sum()
+    }
+
+    #[wasm_bindgen]
+    pub fn reset_drained(&mut self) {
+        self.drained.clear();
+    }
+
+    // Set which element types sensors placed by the brush detect, as a combination of
+    // 1 << ElementType values. Pass 0 to go back to sensors that detect liquids only.
+    #[wasm_bindgen]
+    pub fn set_sensor_detects(&mut self, mask: u32) {
+        self.sensor_detects = if mask == 0 { element::SENSOR.detects() } else { mask };
+    }
+
+    fn sensor_triggered(&mut self, x: usize, y: usize) {
+        self.sensor_events.push((x, y));
+    }
+
+    // The positions of the sensors that switched on since the last call, as x, y pairs
+    #[wasm_bindgen]
+    pub fn take_sensor_events(&mut self) -> Vec<u32> {
+        self.sensor_events
+            .drain(..)
+            .flat_map(|(x, y)| [x as u32, y as u32])
+            .collect()
+    }
+
+    // Set the chance each tick that pipes, pumps and valves placed by the brush move
+    // liquid on, which sets how fast liquid flows through them
+    #[wasm_bindgen]
+    pub fn set_pipe_rate(&mut self, rate: f32) {
+        self.pipe_rate = rate.clamp(0.0, 1.0);
+    }
+
+    // Set the channel of the portals placed by the brush. Particles going into a portal
+    // come out of another one on the same channel.
+    #[wasm_bindgen]
+    pub fn set_portal_channel(&mut self, channel: u8) {
+        self.portal_channel = channel;
+    }
+
+    // The empty cells a particle going into the portal at the given position, moving in
+    // the given direction, could come out at: just past the other portals on the same
+    // channel. The portal's own cells (every portal cell joined to it) don't count.
+    fn portal_exits(&self, x: usize, y: usize, direction_x: isize, direction_y: isize) -> Vec<(usize, usize)> {
+        let channel = match self.get(x, y).portal_channel() {
+            Some(channel) => channel,
+            None => return Vec::new(),
+        };
+        let mut own = HashSet::from([(x, y)]);
+        let mut unvisited = vec![(x, y)];
+        while let Some((cx, cy)) = unvisited.pop() {
+            for ny in cy.saturating_sub(1)..=(cy + 1).min(self.height - 1) {
+                for nx in cx.saturating_sub(1)..=(cx + 1).min(self.width - 1) {
+                    if self.get(nx, ny).portal_channel() == Some(channel) && own.insert((nx, ny)) {
+                        unvisited.push((nx, ny));
+                    }
+                }
+            }
+        }
+        let mut exits = Vec::new();
+        for &index in self.portals.get(&channel).into_iter().flatten() {
+            let (px, py) = (index % self.width, index / self.width);
+            if own.contains(&(px, py)) {
+                continue;
+            }
+            let (exit_x, exit_y) = (px as isize + direction_x, py as isize + direction_y);
+            if exit_x < 0 || exit_y < 0 || !self.is_within_bounds(exit_x as usize, exit_y as usize) {
+                continue;
+            }
+            if self.get(exit_x as usize, exit_y as usize) == element::NOTHING {
+                exits.push((exit_x as usize, exit_y as usize));
+            }
+        }
+        exits
+    }
+
+    // Make dragging the brush paint wind or blow gusts instead of painting elements
+    #[wasm_bindgen]
+    pub fn set_wind_brush(&mut self, mode: WindBrush) {
+        self.wind_brush = mode;
+    }
+
+    // Remove the painted wind field
+    #[wasm_bindgen]
+    pub fn clear_wind(&mut self) {
+        self.wind.clear();
+    }
+
+    // Blow wind along the drag from the previous mouse position to the given one, either
+    // painting it into the wind field or as a one-off gust
+    fn draw_wind(&mut self, brush_offsets: &[(isize, isize)], mouse_pos_x: usize, mouse_pos_y: usize) {
+        let drag_x = mouse_pos_x as f32 - self.previous_mouse_x as f32;
+        let drag_y = mouse_pos_y as f32 - self.previous_mouse_y as f32;
+        let length = (drag_x * drag_x + drag_y * drag_y).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        let points_on_line = self.draw_line(self.previous_mouse_x as isize, self.previous_mouse_y as isize, mouse_pos_x as isize, mouse_pos_y as isize);
+        let mut cells = HashSet::new();
+        for (x, y) in points_on_line {
+            for (offset_x, offset_y) in brush_offsets {
+                let (new_x, new_y) = (x + offset_x, y + offset_y);
+                if new_x >= 0 && new_y >= 0 && self.is_within_bounds(new_x as usize, new_y as usize) {
+                    cells.insert((new_x as usize, new_y as usize));
+                }
+            }
+        }
+        let (direction_x, direction_y) = (drag_x / length, drag_y / length);
+        for (x, y) in cells {
+            match self.wind_brush {
+                WindBrush::Field => {
+                    self.wind.insert(y * self.width + x, (direction_x * WIND_SPEED, direction_y * WIND_SPEED));
+                }
+                WindBrush::Gust => {
+                    let cell = self.get(x, y);
+                    cell.blow(self, x, y, direction_x * GUST_SPEED, direction_y * GUST_SPEED);
+                }
+                WindBrush::Off => {}
+            }
+        }
+    }
+
+    // The element the brush paints: the selected element, or a generator of it when
+    // the generator brush is on
+    fn brush_element(&self) -> element::Element {
+        if let Some(plumbing) = self.selected_element.with_flow_rate(self.pipe_rate) {
+            return plumbing;
+        }
+        if self.selected_element == element::DRAIN {
+            return element::Element::drain(self.drain_absorbs);
+        }
+        if self.selected_element == element::SENSOR {
+            return element::Element::sensor(self.sensor_detects);
+        }
+        if self.selected_element == element::PORTAL {
+            return element::Element::portal(self.portal_channel);
+        }
+        if !self.generator_brush
+            || self.selected_element == element::NOTHING
+            || self.selected_element.element_type == element::ElementType::PixelGenerator
+        {
+            return self.selected_element;
+        }
+        match self.species(&self.selected_element) {
+            Some(emits) => element::Element::generator(emits, self.generator_x, self.generator_y, self.generator_rate),
+            None => self.selected_element,
+        }
     }
 
     fn draw_line(&self, x0: isize, y0: isize, x1: isize, y1: isize) -> Vec<(isize, isize)> {
@@ -127,6 +808,74 @@ impl Grid {
 
     pub fn reset(&mut self) {
         self.elements = vec![element::NOTHING; self.width * self.height];
+        self.portals.clear();
+        self.wind.clear();
+        self.wake_all();
+    }
+
+    // Resize the grid, keeping the existing content pinned to the given anchor.
+    // Anything that no longer fits is cropped and new space is filled with nothing.
+    // Sizes are at least one cell, and sizes over MAX_CELLS are ignored.
+    #[wasm_bindgen]
+    pub fn resize(&mut self, new_width: usize, new_height: usize, anchor: Anchor) {
+        let (new_width, new_height) = (new_width.max(1), new_height.max(1));
+        if new_width.checked_mul(new_height).is_none_or(|cells| cells > MAX_CELLS) {
+            return;
+        }
+        let (offset_x, offset_y) = match anchor {
+            Anchor::TopLeft => (0, 0),
+            Anchor::Centre => (
+                (new_width as isize - self.width as isize) / 2,
+                (new_height as isize - self.height as isize) / 2,
+            ),
+            Anchor::Bottom => (
+                (new_width as isize - self.width as isize) / 2,
+                new_height as isize - self.height as isize,
+            ),
+        };
+        let mut elements = vec![element::NOTHING; new_width * new_height];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let new_x = x as isize + offset_x;
+                let new_y = y as isize + offset_y;
+                if new_x >= 0 && new_y >= 0 && (new_x as usize) < new_width && (new_y as usize) < new_height {
+                    elements[new_y as usize * new_width + new_x as usize] = self.get(x, y);
+                }
+            }
+        }
+        self.width = new_width;
+        self.height = new_height;
+        self.elements = elements;
+        self.stamps = vec![0; new_width * new_height];
+        self.index_portals();
+        // The wind field is stored by cell index, which no longer lines up
+        self.wind.clear();
+        self.build_chunks();
+    }
+
+    // Grow the grid by an integer factor, scaling every cell up into a factor x factor
+    // block. Nothing happens if the grid would end up with more than MAX_CELLS.
+    #[wasm_bindgen]
+    pub fn rescale(&mut self, factor: usize) {
+        if factor < 2 || (self.width * self.height).checked_mul(factor * factor).is_none_or(|cells| cells > MAX_CELLS) {
+            return;
+        }
+        let new_width = self.width * factor;
+        let new_height = self.height * factor;
+        let mut elements = vec![element::NOTHING; new_width * new_height];
+        for y in 0..new_height {
+            for x in 0..new_width {
+                elements[y * new_width + x] = self.get(x / factor, y / factor);
+            }
+        }
+        self.width = new_width;
+        self.height = new_height;
+        self.elements = elements;
+        self.stamps = vec![0; new_width * new_height];
+        self.index_portals();
+        // The wind field is stored by cell index, which no longer lines up
+        self.wind.clear();
+        self.build_chunks();
     }
 
     #[wasm_bindgen]
@@ -135,7 +884,11 @@ impl Grid {
         for y in 0..self.height {
             for x in 0..self.width {
                 let element = self.get(x,y);
-                let color = element.color;
+                let color = if element == element::NOTHING && self.wind.contains_key(&(y * self.width + x)) {
+                    WIND_COLOR
+                } else {
+                    element.color
+                };
                 let color_string = format!("rgb({}, {}, {})", color.r, color.g, color.b);
                 context.set_fill_style(&JsValue::from_str(&color_string));
                 context.fill_rect((x as f64) * cell_size, (y as f64) * cell_size, cell_size, cell_size);
@@ -188,15 +941,22 @@ impl Grid {
             },
             _ => {},
         }
+        if self.wind_brush != WindBrush::Off {
+            self.draw_wind(&brush_offsets, mouse_pos_x, mouse_pos_y);
+            self.previous_mouse_x = mouse_pos_x;
+            self.previous_mouse_y = mouse_pos_y;
+            return;
+        }
+        let brush = self.brush_element();
         let points_on_line = self.draw_line(self.previous_mouse_x as isize, self.previous_mouse_y as isize, mouse_pos_x as isize, mouse_pos_y as isize);
         for point in points_on_line{
             let x1 = point.0;
             let y1 = point.1;
             for offset in brush_offsets.iter() {
                 let new_x:usize = (x1 + offset.0) as usize;
-                let new_y = (y1 as isize + offset.1) as usize;
-                if self.get(new_x,new_y) == element::NOTHING || self.selected_element == element::NOTHING{
-                    self.set(new_x, new_y, self.selected_element);
+                let new_y = (y1 + offset.1) as usize;
+                if self.get(new_x,new_y) == element::NOTHING || brush == element::NOTHING{
+                    self.set(new_x, new_y, brush);
                 }
             }
         }
@@ -221,6 +981,40 @@ impl Grid {
             "m" => self.selected_element = element::MAZE,
             "f" => self.selected_element = element::FAUCET,
             "d" => self.selected_element = element::DRAIN,
+            "o" => self.selected_element = element::OIL,
+            "s" => self.selected_element = element::SMOKE,
+            "l" => self.selected_element = element::WOOD,
+            "p" => self.selected_element = element::PLANT,
+            "n" => self.selected_element = element::SEED,
+            "v" => self.selected_element = element::LAVA,
+            "b" => self.selected_element = element::OBSIDIAN,
+            "h" => self.selected_element = element::STEAM,
+            "a" => self.selected_element = element::ACID,
+            "c" => self.selected_element = element::GLASS,
+            "u" => self.selected_element = element::GUNPOWDER,
+            "x" => self.selected_element = element::C4,
+            "i" => self.selected_element = element::ICE,
+            "k" => self.selected_element = element::SNOW,
+            "j" => self.selected_element = element::CRYO,
+            "1" => self.selected_element = element::METAL,
+            "2" => self.selected_element = element::BATTERY,
+            "3" => self.selected_element = element::NOT_GATE,
+            "4" => self.selected_element = element::AND_GATE,
+            "5" => self.selected_element = element::OR_GATE,
+            "6" => self.selected_element = element::XOR_GATE,
+            "7" => self.selected_element = element::DELAY,
+            "8" => self.selected_element = element::SWITCH,
+            "9" => self.selected_element = element::SENSOR,
+            ";" => self.selected_element = element::CLONE,
+            "-" => self.selected_element = element::VOID,
+            "=" => self.selected_element = element::ATTRACTOR,
+            "<" => self.selected_element = element::CONVEYOR_LEFT,
+            ">" => self.selected_element = element::CONVEYOR_RIGHT,
+            "/" => self.selected_element = element::PIPE,
+            "\\" => self.selected_element = element::PUMP,
+            "'" => self.selected_element = element::VALVE,
+            "`" => self.selected_element = element::PORTAL,
+            "g" => self.generator_brush = !self.generator_brush,
             "[" => {
                 if self.brush_size > 1 {
                     self.brush_size -= 1;
@@ -232,8 +1026,472 @@ impl Grid {
                 }
             }
             "z" => self.reset(),
+            "ArrowDown" => self.set_gravity(0.0, element::GRAVITY),
+            "ArrowUp" => self.set_gravity(0.0, -element::GRAVITY),
+            "ArrowLeft" => self.set_gravity(-element::GRAVITY, 0.0),
+            "ArrowRight" => self.set_gravity(element::GRAVITY, 0.0),
+            "0" => self.set_gravity(0.0, 0.0),
             _ => {self.selected_element = element::STONE}
         }
     }
 
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pour an element onto the middle of a floor for the given number of ticks, over
+    // several trials, and count the cells of it that end up on either side of the pour.
+    // With sideways gravity the grid is turned on its side, so the floor is the
+    // right-hand edge.
+    fn pour(poured: Element, ticks: usize, sideways: bool) -> (usize, usize) {
+        const TRIALS: usize = 10;
+        const CENTRE: usize = 50;
+        // Turn a position across and along gravity into a grid position
+        let cell = |across: usize, along: usize| if sideways { (along, across) } else { (across, along) };
+        let (mut before, mut after) = (0, 0);
+        for _ in 0..TRIALS {
+            let (width, height) = cell(CENTRE * 2 + 1, 60);
+            let mut grid = Grid::new(width, height);
+            if sideways {
+                grid.set_gravity(element::GRAVITY, 0.0);
+            }
+            let (pour_x, pour_y) = cell(CENTRE, 0);
+            for _ in 0..ticks {
+                if grid.get(pour_x, pour_y) == element::NOTHING {
+                    grid.set(pour_x, pour_y, poured);
+                }
+                grid.update();
+            }
+            for y in 0..grid.height() {
+                for x in 0..grid.width() {
+                    if grid.get(x, y).element_type != poured.element_type {
+                        continue;
+                    }
+                    let across = if sideways { y } else { x };
+                    if across < CENTRE {
+                        before += 1;
+                    } else if across > CENTRE {
+                        after += 1;
+                    }
+                }
+            }
+        }
+        (before, after)
+    }
+
+    fn assert_symmetric((before, after): (usize, usize)) {
+        let total = before + after;
+        assert!(before.abs_diff(after) * 20 < total, "{} cells on one side of the pour but {} on the other", before, after);
+    }
+
+    // Sand poured onto the middle of a floor should pile up evenly on both sides
+    #[test]
+    fn central_pour_forms_symmetric_pile() {
+        assert_symmetric(pour(element::SAND, 1500, false));
+    }
+
+    #[test]
+    fn central_pour_forms_symmetric_pile_with_sideways_gravity() {
+        assert_symmetric(pour(element::SAND, 1500, true));
+    }
+
+    // Water still spreading out from a pour shouldn't favour either side
+    #[test]
+    fn central_pour_spreads_water_evenly() {
+        assert_symmetric(pour(element::WATER, 300, false));
+    }
+
+    #[test]
+    fn central_pour_spreads_water_evenly_with_sideways_gravity() {
+        assert_symmetric(pour(element::WATER, 300, true));
+    }
+
+    // A grid with Maze cells at the given positions
+    fn maze(cells: &[(usize, usize)]) -> Grid {
+        let mut grid = Grid::new(12, 12);
+        for &(x, y) in cells {
+            grid.set(x, y, element::MAZE);
+        }
+        grid
+    }
+
+    // The positions of the live automaton cells, row by row
+    fn live_cells(grid: &Grid) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                if let Some((_, 0)) = grid.get(x, y).automaton_state() {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    // In B3/S12345 every cell of a block has three neighbours and no empty cell has
+    // more than two, so it never changes
+    #[test]
+    fn maze_block_is_still() {
+        let block = [(5, 5), (6, 5), (5, 6), (6, 6)];
+        let mut grid = maze(&block);
+        for _ in 0..3 {
+            grid.update();
+            assert_eq!(live_cells(&grid), block);
+        }
+    }
+
+    // The cells above and below the middle of a line of three are born and nothing else
+    // changes. Updating in place would also give birth to the cells beside them, which
+    // only reach three neighbours by counting the newborns.
+    #[test]
+    fn maze_births_do_not_cascade() {
+        let mut grid = maze(&[(5, 5), (6, 5), (7, 5)]);
+        grid.update();
+        assert_eq!(live_cells(&grid), [(6, 4), (5, 5), (6, 5), (7, 5), (6, 6)]);
+    }
+
+    // The middle of a filled square, with eight neighbours, dies and a cell is born beside
+    // each edge, however the grid is scanned
+    #[test]
+    fn maze_generation_is_independent_of_scan_order() {
+        let pattern = [(4, 4), (5, 4), (6, 4), (4, 5), (6, 5), (4, 6), (5, 6), (6, 6), (5, 5)];
+        let expected = [
+            (5, 3), (4, 4), (5, 4), (6, 4), (3, 5), (4, 5),
+            (6, 5), (7, 5), (4, 6), (5, 6), (6, 6), (5, 7),
+        ];
+        let mut generations = Vec::new();
+        for upwards in [false, true] {
+            for odd_tick in [false, true] {
+                let mut grid = maze(&[]);
+                if upwards {
+                    grid.set_gravity(0.0, -element::GRAVITY);
+                }
+                // Rows alternate scan direction with the tick
+                if odd_tick {
+                    grid.update();
+                }
+                for &(x, y) in &pattern {
+                    grid.set(x, y, element::MAZE);
+                }
+                grid.update();
+                generations.push(live_cells(&grid));
+            }
+        }
+        for generation in generations {
+            assert_eq!(generation, expected);
+        }
+    }
+
+    #[test]
+    fn automaton_names_must_be_new() {
+        let mut grid = Grid::new(4, 4);
+        assert!(grid.check_automaton_name("Life").is_ok());
+        for taken in ["Water", "Maze", "", " "] {
+            assert!(grid.check_automaton_name(taken).is_err(), "\"{}\" should be taken", taken);
+        }
+        grid.register_automaton("Life", "B3/S23", 255.0, 255.0, 255.0).unwrap();
+        assert!(grid.check_automaton_name("Life").is_err());
+    }
+
+    // Seeds are heavier than water, so one dropped into a pond sinks and sprouts on the bottom
+    #[test]
+    fn seeds_sprout_at_the_bottom_of_water() {
+        let mut grid = Grid::new(5, 12);
+        for x in 0..5 {
+            grid.set(x, 11, element::STONE);
+            for y in 5..11 {
+                grid.set(x, y, element::WATER);
+            }
+        }
+        grid.set(2, 0, element::SEED);
+        for _ in 0..100 {
+            grid.update();
+            let sprouted = (0..12).find(|&y| (0..5).any(|x| grid.get(x, y).element_type == ElementType::Plant));
+            if let Some(y) = sprouted {
+                assert_eq!(y, 10);
+                return;
+            }
+        }
+        panic!("The seed never sprouted");
+    }
+
+    #[test]
+    fn pipe_rate_is_a_chance() {
+        let mut grid = Grid::new(4, 4);
+        grid.set_pipe_rate(3.0);
+        assert_eq!(grid.pipe_rate, 1.0);
+        grid.set_pipe_rate(-1.0);
+        assert_eq!(grid.pipe_rate, 0.0);
+    }
+
+    // Portal exits come from the index of portals by channel, which has to follow
+    // portals being placed, erased and moved by a resize
+    #[test]
+    fn portal_exits_follow_the_portals_on_a_channel() {
+        let mut grid = Grid::new(10, 10);
+        grid.set(2, 5, Element::portal(1));
+        grid.set(7, 5, Element::portal(1));
+        grid.set(5, 5, Element::portal(2));
+        assert_eq!(grid.portal_exits(2, 5, 0, 1), [(7, 6)]);
+        grid.set(7, 5, element::NOTHING);
+        assert!(grid.portal_exits(2, 5, 0, 1).is_empty());
+        grid.set(7, 5, Element::portal(1));
+        grid.resize(12, 12, Anchor::Bottom);
+        assert_eq!(grid.portal_exits(3, 7, 0, 1), [(8, 8)]);
+        grid.reset();
+        grid.set(3, 7, Element::portal(1));
+        assert!(grid.portal_exits(3, 7, 0, 1).is_empty());
+    }
+
+    // Water poured into one arm of a U-tube rises up the other until both are level
+    #[test]
+    fn water_levels_even_out_in_a_u_tube() {
+        let (width, height) = (23, 30);
+        let mut grid = Grid::new(width, height);
+        // Two arms nine cells wide, joined by a gap under the wall between them
+        for y in 0..height {
+            grid.set(0, y, element::STONE);
+            grid.set(width - 1, y, element::STONE);
+        }
+        for x in 0..width {
+            grid.set(x, height - 1, element::STONE);
+        }
+        for y in 5..height - 3 {
+            for x in 10..13 {
+                grid.set(x, y, element::STONE);
+            }
+        }
+        for y in 8..height - 1 {
+            for x in 1..10 {
+                grid.set(x, y, element::WATER);
+            }
+        }
+        for _ in 0..600 {
+            grid.update();
+        }
+        // The row of the topmost water in each arm
+        let surface = |arm: std::ops::Range<usize>| {
+            (0..height).find(|&y| arm.clone().any(|x| grid.get(x, y).element_type == ElementType::Liquid))
+        };
+        let (left, right) = (surface(1..10).unwrap(), surface(13..width - 1).unwrap());
+        assert!(left.abs_diff(right) <= 1, "left arm surface at row {} but right at row {}", left, right);
+    }
+
+    // A grid with stone at each of the given positions and nothing anywhere else
+    fn stones(width: usize, height: usize, cells: &[(usize, usize)]) -> Grid {
+        let mut grid = Grid::new(width, height);
+        for &(x, y) in cells {
+            grid.set(x, y, element::STONE);
+        }
+        grid
+    }
+
+    // The positions of every cell that isn't empty, row by row
+    fn filled_cells(grid: &Grid) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                if grid.get(x, y) != element::NOTHING {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn resizing_keeps_content_pinned_to_the_anchor() {
+        let corners = [(0, 0), (3, 0), (0, 3), (3, 3)];
+        let mut grid = stones(4, 4, &corners);
+        grid.resize(8, 6, Anchor::TopLeft);
+        assert_eq!((grid.width(), grid.height()), (8, 6));
+        assert_eq!(filled_cells(&grid), corners);
+
+        let mut grid = stones(4, 4, &corners);
+        grid.resize(8, 6, Anchor::Centre);
+        assert_eq!(filled_cells(&grid), [(2, 1), (5, 1), (2, 4), (5, 4)]);
+
+        let mut grid = stones(4, 4, &corners);
+        grid.resize(8, 6, Anchor::Bottom);
+        assert_eq!(filled_cells(&grid), [(2, 2), (5, 2), (2, 5), (5, 5)]);
+    }
+
+    #[test]
+    fn shrinking_crops_what_no_longer_fits() {
+        let corners = [(0, 0), (3, 0), (0, 3), (3, 3)];
+        let mut grid = stones(4, 4, &corners);
+        grid.resize(2, 2, Anchor::TopLeft);
+        assert_eq!(filled_cells(&grid), [(0, 0)]);
+
+        let mut grid = stones(4, 4, &[(1, 0), (0, 3), (1, 3), (2, 3)]);
+        grid.resize(2, 2, Anchor::Bottom);
+        assert_eq!(filled_cells(&grid), [(0, 1), (1, 1)]);
+
+        let mut grid = stones(4, 4, &[(1, 1), (2, 2)]);
+        grid.resize(2, 2, Anchor::Centre);
+        assert_eq!(filled_cells(&grid), [(0, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn rescaling_turns_each_cell_into_a_block() {
+        let mut grid = stones(3, 2, &[(1, 0)]);
+        grid.rescale(2);
+        assert_eq!((grid.width(), grid.height()), (6, 4));
+        assert_eq!(filled_cells(&grid), [(2, 0), (3, 0), (2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn grid_sizes_are_kept_in_bounds() {
+        let mut grid = Grid::new(0, 0);
+        assert_eq!((grid.width(), grid.height()), (1, 1));
+        grid.resize(0, 5, Anchor::TopLeft);
+        assert_eq!((grid.width(), grid.height()), (1, 5));
+        grid.set_gravity(element::GRAVITY, 0.0);
+        grid.update();
+        grid.resize(MAX_CELLS, 2, Anchor::TopLeft);
+        assert_eq!((grid.width(), grid.height()), (1, 5));
+        grid.resize(512, 512, Anchor::TopLeft);
+        grid.rescale(2);
+        assert_eq!((grid.width(), grid.height()), (512, 512));
+    }
+
+    // A grain dropped onto a floor splashes a little sideways but soon stops sliding
+    #[test]
+    fn dropped_grains_come_to_rest_near_where_they_land() {
+        for _ in 0..8 {
+            let mut grid = Grid::new(201, 60);
+            grid.set(100, 0, element::SAND);
+            for _ in 0..200 {
+                grid.update();
+            }
+            let x = (0..grid.width()).find(|&x| grid.get(x, 59).element_type == ElementType::MoveableSolid);
+            assert!(x.is_some_and(|x| x.abs_diff(100) <= 5), "the grain came to rest at {:?}", x);
+        }
+    }
+
+    // A battery sends sparks down a metal wire one cell a tick, setting off the C4 at the end
+    #[test]
+    fn sparks_travel_along_wires() {
+        let mut grid = Grid::new(16, 5);
+        grid.set(0, 2, element::BATTERY);
+        for x in 1..12 {
+            grid.set(x, 2, element::METAL);
+        }
+        grid.set(12, 2, element::C4);
+        for _ in 0..8 {
+            grid.update();
+        }
+        assert!(grid.get(12, 2) == element::C4, "the C4 went off before a spark could reach it");
+        for _ in 0..8 {
+            grid.update();
+        }
+        assert!(grid.get(12, 2) != element::C4, "the spark never reached the C4");
+    }
+
+    // A sensor switches on when water lands next to it and reports that once
+    #[test]
+    fn sensors_report_what_they_detect() {
+        let mut grid = Grid::new(10, 10);
+        grid.set(5, 9, element::SENSOR);
+        grid.set(5, 4, element::WATER);
+        for _ in 0..20 {
+            grid.update();
+        }
+        assert_eq!(grid.take_sensor_events(), [5, 9]);
+        assert!(grid.take_sensor_events().is_empty());
+    }
+
+    // A clone copies the first thing to touch it and keeps making more of it
+    #[test]
+    fn clones_copy_what_touches_them() {
+        let mut grid = Grid::new(10, 10);
+        grid.set(5, 5, element::CLONE);
+        grid.set(5, 2, element::SAND);
+        for _ in 0..30 {
+            grid.update();
+        }
+        let cells: Vec<ElementType> = filled_cells(&grid)
+            .into_iter()
+            .map(|(x, y)| grid.get(x, y).element_type)
+            .filter(|&element_type| element_type != ElementType::Clone)
+            .collect();
+        assert!(cells.len() > 5, "only {} cells besides the clone", cells.len());
+        assert!(cells.iter().all(|&element_type| element_type == ElementType::MoveableSolid));
+    }
+
+    // A grain above an attractor gets pulled in and deleted rather than hanging in place
+    #[test]
+    fn attractors_pull_in_grains_above_them() {
+        let mut grid = Grid::new(21, 30);
+        grid.set(10, 20, element::ATTRACTOR);
+        grid.set(10, 10, element::SAND);
+        for _ in 0..60 {
+            grid.update();
+        }
+        assert_eq!(filled_cells(&grid), vec![(10, 20)]);
+    }
+
+    // Conveyors carry what sits on them one cell per tick in their direction
+    #[test]
+    fn conveyors_carry_particles_along() {
+        for (conveyor, end) in [(element::CONVEYOR_RIGHT, 15), (element::CONVEYOR_LEFT, 5)] {
+            let mut grid = Grid::new(20, 10);
+            for x in 0..20 {
+                grid.set(x, 9, conveyor);
+            }
+            grid.set(10, 8, element::SAND);
+            for _ in 0..5 {
+                grid.update();
+            }
+            let grains: Vec<(usize, usize)> = filled_cells(&grid).into_iter().filter(|&(_, y)| y < 9).collect();
+            assert_eq!(grains, vec![(end, 8)]);
+        }
+    }
+
+    // Drag the wind brush along row 5 from x = 2 to x = 17
+    fn drag_wind(grid: &mut Grid, mode: WindBrush) {
+        grid.brush_size = 1;
+        grid.set_wind_brush(mode);
+        grid.set_mouse(2, 5);
+        grid.draw_mouse(17, 5);
+    }
+
+    // A painted wind field keeps blowing liquid along until it is cleared
+    #[test]
+    fn wind_fields_keep_blowing_until_cleared() {
+        let mut grid = Grid::new(20, 10);
+        grid.set_gravity(0.0, 0.0);
+        drag_wind(&mut grid, WindBrush::Field);
+        grid.set(3, 5, element::WATER);
+        for _ in 0..30 {
+            grid.update();
+        }
+        let (x, _) = filled_cells(&grid)[0];
+        assert!(x >= 15, "only blown to x = {}", x);
+
+        grid.clear_wind();
+        grid.set(x, 5, element::NOTHING);
+        grid.set(3, 5, element::WATER);
+        for _ in 0..30 {
+            grid.update();
+        }
+        assert_eq!(filled_cells(&grid), vec![(3, 5)]);
+    }
+
+    // A gust gives liquid one push without painting anything into the wind field
+    #[test]
+    fn gusts_push_liquid_once() {
+        let mut grid = Grid::new(20, 10);
+        grid.set_gravity(0.0, 0.0);
+        grid.set(3, 5, element::WATER);
+        drag_wind(&mut grid, WindBrush::Gust);
+        assert!(grid.wind.is_empty());
+        assert_eq!(filled_cells(&grid), vec![(3, 5)]);
+        for _ in 0..30 {
+            grid.update();
+        }
+        let (x, _) = filled_cells(&grid)[0];
+        assert!(x > 3, "not blown at all");
+    }
+}